@@ -1,8 +1,73 @@
-use super::types::{Byte, Word};
+use super::types::{Byte, HalfWord, Word};
+use error::ArmError;
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BusError {
+    // Nothing is mapped at the address.
+    Unmapped,
+    // The region does not allow the access, e.g. a write to ROM.
+    Permission,
+    // The region does not support the access at this alignment.
+    Alignment,
+    // The device mapped at the address failed the access.
+    Device,
+    // Translation, domain or permission fault of the MMU, with the fault status and domain.
+    Mmu(Word),
+}
+
+impl BusError {
+    // Data abort of a load or store from `addr`.
+    pub fn data_abort(self, addr: Word) -> ArmError {
+        debug!("{:?} bus error at {:x}", self, addr);
+        ArmError::DataAbort(self, addr)
+    }
+}
+
+// Failed accesses are aborted by the core, a failed fetch raises a prefetch abort
+// and a failed load or store a data abort.
 pub trait Bus {
-    fn read_byte(&self, addr: u32) -> Byte;
-    fn read_word(&self, addr: u32) -> Word;
-    fn write_byte(&mut self, addr: u32, data: u8);
-    fn write_word(&mut self, addr: u32, data: u32);
+    fn read_byte(&self, addr: u32) -> Result<Byte, BusError>;
+    fn read_word(&self, addr: u32) -> Result<Word, BusError>;
+    fn write_byte(&mut self, addr: u32, data: u8) -> Result<(), BusError>;
+    fn write_word(&mut self, addr: u32, data: u32) -> Result<(), BusError>;
+
+    // Halfwords are little endian, buses with 16-bit devices should override these
+    // so that a halfword transfer reaches the device as one access.
+    fn read_halfword(&self, addr: u32) -> Result<HalfWord, BusError> {
+        let low = self.read_byte(addr)? as HalfWord;
+        let high = self.read_byte(addr.wrapping_add(1))? as HalfWord;
+        Ok(low | high << 8)
+    }
+
+    fn write_halfword(&mut self, addr: u32, data: HalfWord) -> Result<(), BusError> {
+        self.write_byte(addr, data as Byte)?;
+        self.write_byte(addr.wrapping_add(1), (data >> 8) as Byte)
+    }
+
+    // LOCK is asserted around the read and write of SWP/SWPB,
+    // so that devices can treat the pair as one atomic transfer.
+    fn lock(&mut self) {}
+    fn unlock(&mut self) {}
+
+    // States whether the following accesses are sequential (S), each to the address
+    // following the previous one, or non-sequential (N). Only buses with wait states care.
+    fn set_sequential(&mut self, _sequential: bool) {}
+
+    // Cycles of the accesses made since the last call, including wait states.
+    // Buses which do not model access timing return zero and a tick takes one cycle.
+    fn take_access_cycles(&mut self) -> u32 {
+        0
+    }
+
+    // Clocks the devices on the bus, called by the core with the cycles of each tick.
+    fn tick(&mut self, _cycles: u32) {}
+
+    // Interrupt outputs of the devices on the bus, ORed with the nIRQ and nFIQ inputs of the core.
+    fn irq(&self) -> bool {
+        false
+    }
+
+    fn fiq(&self) -> bool {
+        false
+    }
 }
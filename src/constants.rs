@@ -15,6 +15,7 @@ pub const COND_LT: u32 = 0xB0000000;
 pub const COND_GT: u32 = 0xC0000000;
 pub const COND_LE: u32 = 0xD0000000;
 pub const COND_AL: u32 = 0xE0000000;
+pub const COND_NV: u32 = 0xF0000000;
 
 // Op
 pub const OP_FIELD: u32 = 0x0C000000;
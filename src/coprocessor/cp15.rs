@@ -0,0 +1,258 @@
+use bus::BusError;
+use coprocessor::Coprocessor;
+use types::Word;
+
+// Main ID and cache type of the ARM920T.
+pub const ARM920T_ID: Word = 0x4112_9200;
+pub const ARM920T_CACHE_TYPE: Word = 0x0D17_2172;
+
+// Status of the fault status registers (c5), the domain is in bits 7-4.
+pub const FSR_ALIGNMENT: Word = 0b0001;
+pub const FSR_EXTERNAL_ABORT: Word = 0b1000;
+pub const FSR_TRANSLATION_SECTION: Word = 0b0101;
+pub const FSR_TRANSLATION_PAGE: Word = 0b0111;
+pub const FSR_DOMAIN_SECTION: Word = 0b1001;
+pub const FSR_DOMAIN_PAGE: Word = 0b1011;
+pub const FSR_PERMISSION_SECTION: Word = 0b1101;
+pub const FSR_PERMISSION_PAGE: Word = 0b1111;
+pub const FSR_EXTERNAL_ABORT_LEVEL1: Word = 0b1100;
+pub const FSR_EXTERNAL_ABORT_LEVEL2: Word = 0b1110;
+
+// Status recorded in the fault status registers for a failed access, anything but
+// alignment and MMU faults is an external abort from the point of view of the core.
+pub fn fault_status(e: BusError) -> Word {
+    match e {
+        BusError::Alignment => FSR_ALIGNMENT,
+        BusError::Mmu(status) => status,
+        _ => FSR_EXTERNAL_ABORT,
+    }
+}
+
+// Bits of the control register (c1).
+pub const CONTROL_M: Word = 0x0000_0001; // MMU enable
+pub const CONTROL_A: Word = 0x0000_0002; // Alignment fault checking
+pub const CONTROL_C: Word = 0x0000_0004; // Data cache enable
+pub const CONTROL_S: Word = 0x0000_0100; // System protection
+pub const CONTROL_R: Word = 0x0000_0200; // ROM protection
+pub const CONTROL_I: Word = 0x0000_1000; // Instruction cache enable
+pub const CONTROL_V: Word = 0x0000_2000; // High exception vectors
+pub const CONTROL_RR: Word = 0x0000_4000; // Round robin replacement
+pub const CONTROL_NF: Word = 0x4000_0000; // nFastBus
+pub const CONTROL_IA: Word = 0x8000_0000; // Asynchronous clock
+
+// Bits 6-3 should be one, bit 7 (B) is fixed to little endian.
+const CONTROL_SBO: Word = 0x0000_0078;
+const CONTROL_MASK: Word = CONTROL_M
+    | CONTROL_A
+    | CONTROL_C
+    | CONTROL_S
+    | CONTROL_R
+    | CONTROL_I
+    | CONTROL_V
+    | CONTROL_RR
+    | CONTROL_NF
+    | CONTROL_IA;
+
+// TLB operations (c8). The TLBs for instructions and data are unified,
+// so operations on either of them apply to both.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TlbOperation {
+    InvalidateAll,
+    // Invalidates the entry which translates the modified virtual address.
+    InvalidateEntry(Word),
+}
+
+// System control coprocessor, attached to the core as CP15.
+// Caches are not modelled, so cache maintenance operations are accepted and ignored.
+#[derive(Debug, Clone)]
+pub struct Cp15 {
+    id: Word,
+    cache_type: Word,
+    control: Word,
+    ttbr: Word,
+    dacr: Word,
+    data_fsr: Word,
+    prefetch_fsr: Word,
+    far: Word,
+    pid: Word,
+    wait_for_interrupt: bool,
+    tlb_operation: Option<TlbOperation>,
+}
+
+impl Default for Cp15 {
+    fn default() -> Self {
+        Cp15::new(ARM920T_ID, ARM920T_CACHE_TYPE)
+    }
+}
+
+impl Cp15 {
+    pub fn new(id: Word, cache_type: Word) -> Self {
+        Cp15 {
+            id,
+            cache_type,
+            control: CONTROL_SBO,
+            ttbr: 0,
+            dacr: 0,
+            data_fsr: 0,
+            prefetch_fsr: 0,
+            far: 0,
+            pid: 0,
+            wait_for_interrupt: false,
+            tlb_operation: None,
+        }
+    }
+
+    pub fn control(&self) -> Word {
+        self.control
+    }
+
+    pub fn is_mmu_enabled(&self) -> bool {
+        self.control & CONTROL_M != 0
+    }
+
+    pub fn is_alignment_check_enabled(&self) -> bool {
+        self.control & CONTROL_A != 0
+    }
+
+    // Exception vectors are at 0xFFFF0000 instead of 0x00000000.
+    pub fn has_high_vectors(&self) -> bool {
+        self.control & CONTROL_V != 0
+    }
+
+    pub fn ttbr(&self) -> Word {
+        self.ttbr
+    }
+
+    pub fn dacr(&self) -> Word {
+        self.dacr
+    }
+
+    pub fn data_fsr(&self) -> Word {
+        self.data_fsr
+    }
+
+    pub fn prefetch_fsr(&self) -> Word {
+        self.prefetch_fsr
+    }
+
+    pub fn far(&self) -> Word {
+        self.far
+    }
+
+    pub fn pid(&self) -> Word {
+        self.pid
+    }
+
+    // Records a data abort, FAR is only updated for data aborts.
+    pub fn set_data_fault(&mut self, status: Word, addr: Word) {
+        self.data_fsr = status;
+        self.far = addr;
+    }
+
+    pub fn set_prefetch_fault(&mut self, status: Word) {
+        self.prefetch_fsr = status;
+    }
+
+    // Returns true once after the wait for interrupt operation was issued.
+    pub fn take_wait_for_interrupt(&mut self) -> bool {
+        let wait = self.wait_for_interrupt;
+        self.wait_for_interrupt = false;
+        wait
+    }
+
+    // The core applies the TLB operation issued by the last MCR to its MMU.
+    pub fn take_tlb_operation(&mut self) -> Option<TlbOperation> {
+        self.tlb_operation.take()
+    }
+}
+
+impl Coprocessor for Cp15 {
+    fn mcr(&mut self, opcode1: Word, crn: usize, crm: usize, opcode2: Word, data: Word) -> bool {
+        if opcode1 != 0 {
+            return false;
+        }
+        match crn {
+            // ID registers are read only.
+            0 => {}
+            1 => self.control = (data & CONTROL_MASK) | CONTROL_SBO,
+            2 => self.ttbr = data & 0xFFFF_C000,
+            3 => self.dacr = data,
+            5 if opcode2 == 0 => self.data_fsr = data & 0x1FF,
+            5 if opcode2 == 1 => self.prefetch_fsr = data & 0x1FF,
+            6 => self.far = data,
+            7 => {
+                if crm == 0 && opcode2 == 4 {
+                    debug!("cp15 wait for interrupt");
+                    self.wait_for_interrupt = true;
+                }
+            }
+            8 => {
+                self.tlb_operation = Some(if opcode2 == 1 {
+                    TlbOperation::InvalidateEntry(data)
+                } else {
+                    TlbOperation::InvalidateAll
+                })
+            }
+            // Cache and TLB lockdown and test registers.
+            9 | 10 | 15 => {}
+            13 => self.pid = data & 0xFE00_0000,
+            _ => return false,
+        }
+        true
+    }
+
+    fn mrc(&mut self, opcode1: Word, crn: usize, _crm: usize, opcode2: Word) -> Option<Word> {
+        if opcode1 != 0 {
+            return None;
+        }
+        match crn {
+            0 if opcode2 == 1 => Some(self.cache_type),
+            0 => Some(self.id),
+            1 => Some(self.control),
+            2 => Some(self.ttbr),
+            3 => Some(self.dacr),
+            5 if opcode2 == 1 => Some(self.prefetch_fsr),
+            5 => Some(self.data_fsr),
+            6 => Some(self.far),
+            9 | 10 | 15 => Some(0),
+            13 => Some(self.pid),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn control_register_keeps_sbo_bits() {
+    let mut cp15 = Cp15::default();
+    assert!(cp15.mcr(0, 1, 0, 0, CONTROL_M | CONTROL_V | 0x80));
+    assert_eq!(cp15.mrc(0, 1, 0, 0), Some(CONTROL_M | CONTROL_V | 0x78));
+    assert!(cp15.is_mmu_enabled());
+    assert!(cp15.has_high_vectors());
+}
+
+#[test]
+fn ttbr_is_16kb_aligned() {
+    let mut cp15 = Cp15::default();
+    assert!(cp15.mcr(0, 2, 0, 0, 0x3000_5FFF));
+    assert_eq!(cp15.ttbr(), 0x3000_4000);
+}
+
+#[test]
+fn tlb_operations_are_taken_once() {
+    let mut cp15 = Cp15::default();
+    assert!(cp15.mcr(0, 8, 7, 0, 0));
+    assert_eq!(cp15.take_tlb_operation(), Some(TlbOperation::InvalidateAll));
+    assert!(cp15.mcr(0, 8, 6, 1, 0x1234_5000));
+    assert_eq!(
+        cp15.take_tlb_operation(),
+        Some(TlbOperation::InvalidateEntry(0x1234_5000))
+    );
+    assert_eq!(cp15.take_tlb_operation(), None);
+}
+
+#[test]
+fn unknown_registers_are_rejected() {
+    let mut cp15 = Cp15::default();
+    assert!(!cp15.mcr(0, 4, 0, 0, 0));
+    assert_eq!(cp15.mrc(1, 0, 0, 0), None);
+}
@@ -0,0 +1,48 @@
+pub mod cp15;
+
+use types::Word;
+
+// A coprocessor attached to the core by its number.
+// Every operation answers false (or None) when the coprocessor does not accept
+// the instruction, then the core takes the undefined instruction exception.
+pub trait Coprocessor {
+    // CDP, internal operation of the coprocessor.
+    fn cdp(
+        &mut self,
+        _opcode1: Word,
+        _crd: usize,
+        _crn: usize,
+        _crm: usize,
+        _opcode2: Word,
+    ) -> bool {
+        false
+    }
+
+    // MCR, `data` is the value of the ARM register.
+    fn mcr(
+        &mut self,
+        _opcode1: Word,
+        _crn: usize,
+        _crm: usize,
+        _opcode2: Word,
+        _data: Word,
+    ) -> bool {
+        false
+    }
+
+    // MRC, the value is written to the ARM register, or to N, Z, C and V when it is PC.
+    fn mrc(&mut self, _opcode1: Word, _crn: usize, _crm: usize, _opcode2: Word) -> Option<Word> {
+        None
+    }
+
+    // LDC, `load(n)` reads the nth word from the transfer address.
+    // The coprocessor decides how many words are transferred.
+    fn ldc(&mut self, _crd: usize, _long: bool, _load: &mut FnMut(usize) -> Word) -> bool {
+        false
+    }
+
+    // STC, `store(n, data)` writes the nth word to the transfer address.
+    fn stc(&mut self, _crd: usize, _long: bool, _store: &mut FnMut(usize, Word)) -> bool {
+        false
+    }
+}
@@ -1,4 +1,4 @@
-use constants::COND_FIELD;
+use constants::*;
 use types::{Shift, Word};
 
 #[derive(Debug, PartialEq, Clone)]
@@ -9,7 +9,14 @@ pub enum Category {
     ExtraMemory,
     DataProcessing,
     Branch,
+    BranchAndExchange,
     MultiLoadAndStore,
+    PSRTransfer,
+    Swap,
+    SWI,
+    CoprocessorDataTransfer,
+    CoprocessorDataOperation,
+    CoprocessorRegisterTransfer,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -51,14 +58,80 @@ pub enum Opcode {
     LDRSH,
     B,
     BL,
+    BX,
     LDM,
     STM,
+    MRS,
+    MSR,
+    SWP,
+    SWPB,
+    LDC,
+    STC,
+    CDP,
+    MCR,
+    MRC,
     Undefined,
-    // SWI,
+    SWI,
     NOP,
+    #[cfg(feature = "armv5te")]
+    CLZ,
+    #[cfg(feature = "armv5te")]
+    BLX,
+    #[cfg(feature = "armv5te")]
+    LDRD,
+    #[cfg(feature = "armv5te")]
+    STRD,
+    #[cfg(feature = "armv5te")]
+    PLD,
+    #[cfg(feature = "armv5te")]
+    QADD,
+    #[cfg(feature = "armv5te")]
+    QSUB,
+    #[cfg(feature = "armv5te")]
+    QDADD,
+    #[cfg(feature = "armv5te")]
+    QDSUB,
+    #[cfg(feature = "armv5te")]
+    SMLAXY,
+    #[cfg(feature = "armv5te")]
+    SMLAWY,
+    #[cfg(feature = "armv5te")]
+    SMULWY,
+    #[cfg(feature = "armv5te")]
+    SMLALXY,
+    #[cfg(feature = "armv5te")]
+    SMULXY,
+    #[cfg(feature = "armv5te")]
+    BKPT,
 }
 
-#[derive(Debug, PartialEq)]
+impl Opcode {
+    // Data processing operations which write Rd, compare operations have no Rd.
+    pub fn is_data_processing_with_rd(&self) -> bool {
+        match *self {
+            Opcode::AND
+            | Opcode::EOR
+            | Opcode::SUB
+            | Opcode::RSB
+            | Opcode::ADD
+            | Opcode::ADC
+            | Opcode::SBC
+            | Opcode::RSC
+            | Opcode::ORR
+            | Opcode::MOV
+            | Opcode::LSL
+            | Opcode::LSR
+            | Opcode::ASR
+            | Opcode::RRX
+            | Opcode::ROR
+            | Opcode::BIC
+            | Opcode::MVN => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Condition {
     EQ,
     NE,
@@ -75,6 +148,8 @@ pub enum Condition {
     GT,
     LE,
     AL,
+    // NOTE: Reserved on ARMv4, never executed.
+    NV,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -97,6 +172,8 @@ pub struct BaseDecoder {
 pub struct MultipleDecoder(BaseDecoder);
 #[derive(Debug)]
 pub struct ExtraMemoryDecoder(BaseDecoder);
+#[derive(Debug)]
+pub struct CoprocessorDataOperationDecoder(BaseDecoder);
 // #[derive(Debug)]
 // pub struct MultiLoadAndStoreDecoder(BaseDecoder);
 
@@ -127,6 +204,7 @@ fn get_S(raw: Word) -> Word {
 pub trait Raw {
     fn raw(&self) -> u32;
     fn op(&self) -> Opcode;
+    fn cond(&self) -> Condition;
 }
 
 pub trait Decoder: Raw {
@@ -157,11 +235,21 @@ pub trait Decoder: Raw {
         self.raw() as i32 & 0xff_ffff
     }
 
+    // Comment field of SWI, ignored by the processor.
+    fn get_comment(&self) -> Word {
+        self.raw() & 0xff_ffff
+    }
+
     #[allow(non_snake_case)]
     fn has_I(&self) -> bool {
         self.raw() & 0x0200_0000 != 0
     }
 
+    #[allow(non_snake_case)]
+    fn has_S(&self) -> bool {
+        self.raw() & 0x0010_0000 != 0
+    }
+
     fn is_pre_indexed(&self) -> bool {
         (self.raw() & (1 << 24)) != 0
     }
@@ -222,6 +310,16 @@ pub trait Decoder: Raw {
     //     self.raw & 0x0040_0000 != 0
     // }
 
+    // Bit: 22, MRS and MSR access SPSR instead of CPSR.
+    fn is_spsr(&self) -> bool {
+        self.raw() & 0x0040_0000 != 0
+    }
+
+    // Bit: 19-16, the <f|s|x|c> fields written by MSR.
+    fn get_field_mask(&self) -> u32 {
+        (self.raw() & 0x000F_0000) >> 16
+    }
+
     // Bit: 23
     fn is_plus_offset(&self) -> bool {
         self.raw() & 0x0080_0000 != 0
@@ -235,6 +333,43 @@ pub trait Decoder: Raw {
         !self.is_plus_offset()
     }
 
+    // Bit: 22, ^ of LDM and STM, user bank transfer or CPSR restore when PC is loaded.
+    fn is_force_user(&self) -> bool {
+        self.raw() & 0x0040_0000 != 0
+    }
+
+    // Bit: 11-8, number of the coprocessor addressed.
+    fn get_cp_num(&self) -> usize {
+        (self.raw() as usize >> 8) & 0b1111
+    }
+
+    // Bit: 23-21, coprocessor operation of MCR and MRC.
+    fn get_cp_opc(&self) -> Word {
+        (self.raw() >> 21) & 0b111
+    }
+
+    // Bit: 7-5, coprocessor information.
+    fn get_cp_info(&self) -> Word {
+        (self.raw() >> 5) & 0b111
+    }
+
+    // Bit: 22, N bit of LDC and STC, meaning is up to the coprocessor.
+    fn is_long(&self) -> bool {
+        self.raw() & 0x0040_0000 != 0
+    }
+
+    // Bit: 5, x of SMLA<x><y>, the top halfword of the first operand is used.
+    #[cfg(feature = "armv5te")]
+    fn is_top_x(&self) -> bool {
+        self.raw() & 0x0000_0020 != 0
+    }
+
+    // Bit: 6, y of SMLA<x><y>, the top halfword of the second operand is used.
+    #[cfg(feature = "armv5te")]
+    fn is_top_y(&self) -> bool {
+        self.raw() & 0x0000_0040 != 0
+    }
+
     // fn is_branch_with_link(&self) -> bool {
     //     self.raw & 0x0100_0000 != 0
     // }
@@ -276,6 +411,13 @@ impl Decoder for ExtraMemoryDecoder {
     }
 }
 
+impl Decoder for CoprocessorDataOperationDecoder {
+    // Bit: 23-20, CDP has a 4 bit operation.
+    fn get_cp_opc(&self) -> Word {
+        (self.raw() >> 20) & 0b1111
+    }
+}
+
 // impl MultiLoadAndStoreDecoder {
 //     #[allow(non_snake_case)]
 //     fn get_register_list(&self) -> usize {
@@ -291,6 +433,10 @@ impl Raw for BaseDecoder {
     fn op(&self) -> Opcode {
         self.opcode.clone()
     }
+
+    fn cond(&self) -> Condition {
+        self.cond
+    }
 }
 
 impl Raw for MultipleDecoder {
@@ -301,6 +447,10 @@ impl Raw for MultipleDecoder {
     fn op(&self) -> Opcode {
         self.0.opcode.clone()
     }
+
+    fn cond(&self) -> Condition {
+        self.0.cond
+    }
 }
 
 impl Raw for ExtraMemoryDecoder {
@@ -311,6 +461,24 @@ impl Raw for ExtraMemoryDecoder {
     fn op(&self) -> Opcode {
         self.0.opcode.clone()
     }
+
+    fn cond(&self) -> Condition {
+        self.0.cond
+    }
+}
+
+impl Raw for CoprocessorDataOperationDecoder {
+    fn raw(&self) -> u32 {
+        self.0.raw
+    }
+
+    fn op(&self) -> Opcode {
+        self.0.opcode.clone()
+    }
+
+    fn cond(&self) -> Condition {
+        self.0.cond
+    }
 }
 
 // impl Raw for MultiLoadAndStoreDecoder {
@@ -332,7 +500,7 @@ fn decode_multiple(raw: Word) -> Opcode {
         0b0101 => Opcode::UMLAL,
         0b0110 => Opcode::SMULL,
         0b0111 => Opcode::SMLAL,
-        _ => unimplemented!(),
+        _ => Opcode::Undefined,
     }
 }
 
@@ -353,7 +521,7 @@ fn decode_extra_memory(raw: Word) -> Opcode {
         0b01 if l => Opcode::LDRH,
         0b10 if l => Opcode::LDRSB,
         0b11 if l => Opcode::LDRSH,
-        _ => panic!("undefined instruction detected"),
+        _ => Opcode::Undefined,
     }
 }
 
@@ -386,7 +554,24 @@ fn decode_data_processing(raw: Word) -> Opcode {
         0b1101 if !I && sh == 0b11 && instr != 0 => Opcode::ROR,
         0b1110 => Opcode::BIC,
         0b1111 => Opcode::MVN,
-        _ => panic!("unsupported instruction"),
+        // TST, TEQ, CMP and CMN without S, which are not PSR transfers.
+        _ => Opcode::Undefined,
+    }
+}
+
+fn decode_swap(raw: Word) -> Opcode {
+    if raw & 0x0040_0000 != 0 {
+        Opcode::SWPB
+    } else {
+        Opcode::SWP
+    }
+}
+
+fn decode_psr_transfer(raw: Word) -> Opcode {
+    if raw & 0x0020_0000 != 0 {
+        Opcode::MSR
+    } else {
+        Opcode::MRS
     }
 }
 
@@ -398,6 +583,22 @@ fn decode_multi_load_and_store(raw: Word) -> Opcode {
     }
 }
 
+fn decode_coprocessor_data_transfer(raw: Word) -> Opcode {
+    if is_load(raw) {
+        Opcode::LDC
+    } else {
+        Opcode::STC
+    }
+}
+
+fn decode_coprocessor_register_transfer(raw: Word) -> Opcode {
+    if is_load(raw) {
+        Opcode::MRC
+    } else {
+        Opcode::MCR
+    }
+}
+
 fn decode_branch(raw: Word) -> Opcode {
     let with_link = raw & 0x0100_0000 != 0;
     if with_link {
@@ -407,25 +608,115 @@ fn decode_branch(raw: Word) -> Opcode {
     }
 }
 
-pub fn decode(raw: Word) -> Box<Decoder> {
-    let cond = raw & COND_FIELD;
-    let cond = match cond {
+pub fn decode_cond(raw: Word) -> Condition {
+    match raw & COND_FIELD {
+        COND_EQ => Condition::EQ,
+        COND_NE => Condition::NE,
+        COND_CS_HS => Condition::CS_HS,
+        COND_CC_LO => Condition::CC_LO,
+        COND_MI => Condition::MI,
+        COND_PL => Condition::PL,
+        COND_VS => Condition::VS,
+        COND_VC => Condition::VC,
+        COND_HI => Condition::HI,
+        COND_LS => Condition::LS,
+        COND_GE => Condition::GE,
+        COND_LT => Condition::LT,
+        COND_GT => Condition::GT,
+        COND_LE => Condition::LE,
         COND_AL => Condition::AL,
-        _ => panic!("Unknowm condition {}", cond),
+        _ => Condition::NV,
+    }
+}
+
+#[cfg(feature = "armv5te")]
+fn decode_saturating(raw: Word) -> Opcode {
+    match (raw >> 21) & 0b11 {
+        0b00 => Opcode::QADD,
+        0b01 => Opcode::QSUB,
+        0b10 => Opcode::QDADD,
+        _ => Opcode::QDSUB,
+    }
+}
+
+#[cfg(feature = "armv5te")]
+fn decode_signed_multiply(raw: Word) -> Opcode {
+    let x = raw & 0x0000_0020 != 0;
+    match (raw >> 21) & 0b11 {
+        0b00 => Opcode::SMLAXY,
+        0b01 if x => Opcode::SMULWY,
+        0b01 => Opcode::SMLAWY,
+        0b10 => Opcode::SMLALXY,
+        _ => Opcode::SMULXY,
+    }
+}
+
+fn new_decoder(category: Category, dec: BaseDecoder) -> Box<Decoder> {
+    match category {
+        Category::Multiple => Box::new(MultipleDecoder(dec)),
+        Category::ExtraMemory => Box::new(ExtraMemoryDecoder(dec)),
+        Category::CoprocessorDataOperation => Box::new(CoprocessorDataOperationDecoder(dec)),
+        _ => Box::new(dec),
+    }
+}
+
+// ARMv5TE instructions are encoded in the ARMv4 undefined and NV spaces,
+// everything else is decoded as ARMv4.
+#[cfg(feature = "armv5te")]
+pub fn decode_armv5te(raw: Word) -> Box<Decoder> {
+    let (category, opcode) = match raw {
+        v if (v & 0xFE00_0000) == 0xFA00_0000 => (Category::Branch, Opcode::BLX),
+        v if (v & 0xFD70_F000) == 0xF550_F000 => (Category::Memory, Opcode::PLD),
+        v if (v & 0xFFF0_00F0) == 0xE120_0070 => (Category::Undefined, Opcode::BKPT),
+        v if decode_cond(v) == Condition::NV => return decode(raw),
+        v if (v & 0x0FFF_FFF0) == 0x012F_FF30 => (Category::BranchAndExchange, Opcode::BLX),
+        v if (v & 0x0FFF_0FF0) == 0x016F_0F10 => (Category::DataProcessing, Opcode::CLZ),
+        v if (v & 0x0F90_0FF0) == 0x0100_0050 => (Category::DataProcessing, decode_saturating(v)),
+        v if (v & 0x0F90_0090) == 0x0100_0080 => (Category::Multiple, decode_signed_multiply(v)),
+        v if (v & 0x0E10_00F0) == 0x0000_00D0 => (Category::ExtraMemory, Opcode::LDRD),
+        v if (v & 0x0E10_00F0) == 0x0000_00F0 => (Category::ExtraMemory, Opcode::STRD),
+        _ => return decode(raw),
+    };
+    debug!("opcode = {:?}", opcode);
+    // INFO: BLX (immediate) and PLD are unconditional, they are executed as AL.
+    let cond = match decode_cond(raw) {
+        Condition::NV => Condition::AL,
+        cond => cond,
     };
+    new_decoder(category, BaseDecoder { raw, cond, opcode })
+}
+
+pub fn decode(raw: Word) -> Box<Decoder> {
+    let cond = decode_cond(raw);
+    // INFO: The NV space is reserved on ARMv4, so do not try to decode it.
+    if cond == Condition::NV {
+        return Box::new(BaseDecoder {
+            raw,
+            cond,
+            opcode: Opcode::NOP,
+        });
+    }
 
     let category = match raw {
         v if (v & 0x0E00_0000) == 0x0A00_0000 => Category::Branch,
+        v if (v & 0x0FB0_0FF0) == 0x0100_0090 => Category::Swap,
         v if (v & 0x0FC0_00F0) == 0x0000_0090 => Category::Multiple,
         v if (v & 0x0F80_00F0) == 0x0080_0090 => Category::Multiple,
         v if (v & 0x0E00_0010) == 0x0600_0010 => Category::Undefined,
         v if (v & 0x0E40_0F90) == 0x0000_0090 => Category::ExtraMemory,
         v if (v & 0x0E40_0090) == 0x0040_0090 => Category::ExtraMemory,
+        v if (v & 0x0FFF_FFF0) == 0x012F_FF10 => Category::BranchAndExchange,
+        v if (v & 0x0FBF_0FFF) == 0x010F_0000 => Category::PSRTransfer, // MRS
+        v if (v & 0x0FB0_FFF0) == 0x0120_F000 => Category::PSRTransfer, // MSR register
+        v if (v & 0x0FB0_F000) == 0x0320_F000 => Category::PSRTransfer, // MSR immediate
         v if (v & 0x0C00_0000) == 0x0400_0000 => Category::Memory,
         v if (v & 0x0C00_0000) == 0x0000_0000 => Category::DataProcessing,
         v if (v & 0x0E00_0000) == 0x0800_0000 => Category::MultiLoadAndStore, // LDM and STM,
-        // v if (v & 0x0F00_0000) == 0x0F00_0000 => Category::SWI,
-        _ => panic!("Unsupported instruction"),
+        v if (v & 0x0E00_0000) == 0x0C00_0000 => Category::CoprocessorDataTransfer,
+        v if (v & 0x0F00_0010) == 0x0E00_0000 => Category::CoprocessorDataOperation,
+        v if (v & 0x0F00_0010) == 0x0E00_0010 => Category::CoprocessorRegisterTransfer,
+        v if (v & 0x0F00_0000) == 0x0F00_0000 => Category::SWI,
+        _ => Category::Undefined,
     };
 
     let opcode = match category {
@@ -435,16 +726,16 @@ pub fn decode(raw: Word) -> Box<Decoder> {
         Category::ExtraMemory => decode_extra_memory(raw),
         Category::DataProcessing => decode_data_processing(raw),
         Category::Branch => decode_branch(raw),
+        Category::BranchAndExchange => Opcode::BX,
         Category::MultiLoadAndStore => decode_multi_load_and_store(raw),
-        // v if (v & 0x0F00_0000) == 0x0F00_0000 => Opcode::SWI,
-        _ => panic!("unsupported instruction"),
+        Category::PSRTransfer => decode_psr_transfer(raw),
+        Category::Swap => decode_swap(raw),
+        Category::SWI => Opcode::SWI,
+        Category::CoprocessorDataTransfer => decode_coprocessor_data_transfer(raw),
+        Category::CoprocessorDataOperation => Opcode::CDP,
+        Category::CoprocessorRegisterTransfer => decode_coprocessor_register_transfer(raw),
     };
 
     debug!("opcode = {:?}", opcode);
-    let dec = BaseDecoder { raw, cond, opcode };
-    match category {
-        Category::Multiple => Box::new(MultipleDecoder(dec)),
-        Category::ExtraMemory => Box::new(ExtraMemoryDecoder(dec)),
-        _ => Box::new(dec),
-    }
+    new_decoder(category, BaseDecoder { raw, cond, opcode })
 }
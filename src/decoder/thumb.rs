@@ -0,0 +1,567 @@
+use decoder::arm::{decode_cond, Condition};
+use types::{HalfWord, Word};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Format {
+    MoveShiftedRegister,
+    AddSubtract,
+    Immediate,
+    ALU,
+    HiRegister,
+    PCRelativeLoad,
+    LoadStoreRegisterOffset,
+    LoadStoreSignExtended,
+    LoadStoreImmediateOffset,
+    LoadStoreHalfWord,
+    SPRelativeLoadStore,
+    LoadAddress,
+    AddOffsetToSP,
+    PushPop,
+    MultiLoadAndStore,
+    ConditionalBranch,
+    SWI,
+    UnconditionalBranch,
+    LongBranchWithLink,
+    Undefined,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Opcode {
+    LSL,
+    LSR,
+    ASR,
+    ADD,
+    SUB,
+    MOV,
+    CMP,
+    AND,
+    EOR,
+    ADC,
+    SBC,
+    ROR,
+    TST,
+    NEG,
+    CMN,
+    ORR,
+    MUL,
+    BIC,
+    MVN,
+    BX,
+    LDR,
+    STR,
+    LDRB,
+    STRB,
+    STRH,
+    LDRH,
+    LDSB,
+    LDSH,
+    PUSH,
+    POP,
+    LDMIA,
+    STMIA,
+    B,
+    BL,
+    SWI,
+    Undefined,
+}
+
+#[derive(Debug)]
+pub struct BaseDecoder {
+    pub format: Format,
+    pub opcode: Opcode,
+    pub raw: HalfWord,
+}
+
+// Formats which keep Rd (or Rb) in bits 10-8 next to an 8 bit immediate or register list.
+#[derive(Debug)]
+pub struct ImmediateDecoder(BaseDecoder);
+// Format 5, Rd and Rs are extended to R8-R15 by H1 and H2.
+#[derive(Debug)]
+pub struct HiRegisterDecoder(BaseDecoder);
+
+pub trait Raw {
+    fn raw(&self) -> HalfWord;
+    fn op(&self) -> Opcode;
+    fn fmt(&self) -> Format;
+}
+
+pub trait Decoder: Raw {
+    fn opcode(&self) -> Opcode {
+        self.op()
+    }
+
+    fn format(&self) -> Format {
+        self.fmt()
+    }
+
+    #[allow(non_snake_case)]
+    fn get_Rd(&self) -> usize {
+        self.raw() as usize & 0b111
+    }
+
+    #[allow(non_snake_case)]
+    fn get_Rs(&self) -> usize {
+        (self.raw() as usize >> 3) & 0b111
+    }
+
+    #[allow(non_snake_case)]
+    fn get_Rb(&self) -> usize {
+        (self.raw() as usize >> 3) & 0b111
+    }
+
+    #[allow(non_snake_case)]
+    fn get_Rn(&self) -> usize {
+        (self.raw() as usize >> 6) & 0b111
+    }
+
+    #[allow(non_snake_case)]
+    fn get_Ro(&self) -> usize {
+        (self.raw() as usize >> 6) & 0b111
+    }
+
+    fn get_offset3(&self) -> Word {
+        (self.raw() as Word >> 6) & 0b111
+    }
+
+    fn get_offset5(&self) -> Word {
+        (self.raw() as Word >> 6) & 0b1_1111
+    }
+
+    fn get_imm8(&self) -> Word {
+        self.raw() as Word & 0xFF
+    }
+
+    // Bit: 10, format 2 takes offset3 instead of Rn.
+    #[allow(non_snake_case)]
+    fn has_I(&self) -> bool {
+        self.raw() & 0x0400 != 0
+    }
+
+    // Bit: 11
+    fn is_load(&self) -> bool {
+        self.raw() & 0x0800 != 0
+    }
+
+    // Bit: 11, format 12 adds to SP instead of PC.
+    fn is_sp(&self) -> bool {
+        self.raw() & 0x0800 != 0
+    }
+
+    // Bit: 8, PUSH stores LR and POP loads PC.
+    #[allow(non_snake_case)]
+    fn has_R(&self) -> bool {
+        self.raw() & 0x0100 != 0
+    }
+
+    fn get_register_list(&self) -> Word {
+        self.raw() as Word & 0xFF
+    }
+
+    // Format 13, SP <- SP +/- (SWord7 << 2).
+    fn get_sp_offset(&self) -> i32 {
+        let offset = (self.raw() as i32 & 0x7F) << 2;
+        if self.raw() & 0x0080 != 0 {
+            -offset
+        } else {
+            offset
+        }
+    }
+
+    fn get_cond(&self) -> Condition {
+        decode_cond((self.raw() as Word & 0x0F00) << 20)
+    }
+
+    // Format 16, sign extended offset in halfwords.
+    fn get_soffset8(&self) -> i32 {
+        (self.raw() as i32 & 0xFF) << 24 >> 23
+    }
+
+    fn get_offset11(&self) -> Word {
+        self.raw() as Word & 0x07FF
+    }
+
+    // Format 18, sign extended offset in halfwords.
+    fn get_soffset11(&self) -> i32 {
+        (self.raw() as i32 & 0x07FF) << 21 >> 20
+    }
+
+    // Bit: 11, 0: first half of BL with the high part of the offset 1: second half.
+    fn is_bl_low(&self) -> bool {
+        self.raw() & 0x0800 != 0
+    }
+}
+
+impl Decoder for BaseDecoder {}
+
+impl Decoder for ImmediateDecoder {
+    #[allow(non_snake_case)]
+    fn get_Rd(&self) -> usize {
+        (self.raw() as usize >> 8) & 0b111
+    }
+
+    #[allow(non_snake_case)]
+    fn get_Rb(&self) -> usize {
+        (self.raw() as usize >> 8) & 0b111
+    }
+}
+
+impl Decoder for HiRegisterDecoder {
+    #[allow(non_snake_case)]
+    fn get_Rd(&self) -> usize {
+        ((self.raw() as usize & 0x0080) >> 4) | (self.raw() as usize & 0b111)
+    }
+
+    #[allow(non_snake_case)]
+    fn get_Rs(&self) -> usize {
+        (self.raw() as usize >> 3) & 0b1111
+    }
+}
+
+impl Raw for BaseDecoder {
+    fn raw(&self) -> HalfWord {
+        self.raw
+    }
+
+    fn op(&self) -> Opcode {
+        self.opcode.clone()
+    }
+
+    fn fmt(&self) -> Format {
+        self.format.clone()
+    }
+}
+
+impl Raw for ImmediateDecoder {
+    fn raw(&self) -> HalfWord {
+        self.0.raw
+    }
+
+    fn op(&self) -> Opcode {
+        self.0.opcode.clone()
+    }
+
+    fn fmt(&self) -> Format {
+        self.0.format.clone()
+    }
+}
+
+impl Raw for HiRegisterDecoder {
+    fn raw(&self) -> HalfWord {
+        self.0.raw
+    }
+
+    fn op(&self) -> Opcode {
+        self.0.opcode.clone()
+    }
+
+    fn fmt(&self) -> Format {
+        self.0.format.clone()
+    }
+}
+
+fn decode_move_shifted_register(raw: HalfWord) -> Opcode {
+    match (raw >> 11) & 0b11 {
+        0b00 => Opcode::LSL,
+        0b01 => Opcode::LSR,
+        _ => Opcode::ASR,
+    }
+}
+
+fn decode_add_subtract(raw: HalfWord) -> Opcode {
+    if raw & 0x0200 != 0 {
+        Opcode::SUB
+    } else {
+        Opcode::ADD
+    }
+}
+
+fn decode_immediate(raw: HalfWord) -> Opcode {
+    match (raw >> 11) & 0b11 {
+        0b00 => Opcode::MOV,
+        0b01 => Opcode::CMP,
+        0b10 => Opcode::ADD,
+        _ => Opcode::SUB,
+    }
+}
+
+fn decode_alu(raw: HalfWord) -> Opcode {
+    match (raw >> 6) & 0b1111 {
+        0b0000 => Opcode::AND,
+        0b0001 => Opcode::EOR,
+        0b0010 => Opcode::LSL,
+        0b0011 => Opcode::LSR,
+        0b0100 => Opcode::ASR,
+        0b0101 => Opcode::ADC,
+        0b0110 => Opcode::SBC,
+        0b0111 => Opcode::ROR,
+        0b1000 => Opcode::TST,
+        0b1001 => Opcode::NEG,
+        0b1010 => Opcode::CMP,
+        0b1011 => Opcode::CMN,
+        0b1100 => Opcode::ORR,
+        0b1101 => Opcode::MUL,
+        0b1110 => Opcode::BIC,
+        _ => Opcode::MVN,
+    }
+}
+
+fn decode_hi_register(raw: HalfWord) -> Opcode {
+    match (raw >> 8) & 0b11 {
+        0b00 => Opcode::ADD,
+        0b01 => Opcode::CMP,
+        0b10 => Opcode::MOV,
+        _ => Opcode::BX,
+    }
+}
+
+fn decode_load_store_register_offset(raw: HalfWord) -> Opcode {
+    match (raw >> 10) & 0b11 {
+        0b00 => Opcode::STR,
+        0b01 => Opcode::STRB,
+        0b10 => Opcode::LDR,
+        _ => Opcode::LDRB,
+    }
+}
+
+fn decode_load_store_sign_extended(raw: HalfWord) -> Opcode {
+    match (raw >> 10) & 0b11 {
+        0b00 => Opcode::STRH,
+        0b01 => Opcode::LDSB,
+        0b10 => Opcode::LDRH,
+        _ => Opcode::LDSH,
+    }
+}
+
+fn decode_load_store_immediate_offset(raw: HalfWord) -> Opcode {
+    match (raw >> 11) & 0b11 {
+        0b00 => Opcode::STR,
+        0b01 => Opcode::LDR,
+        0b10 => Opcode::STRB,
+        _ => Opcode::LDRB,
+    }
+}
+
+fn decode_load_or_store(raw: HalfWord, load: Opcode, store: Opcode) -> Opcode {
+    if raw & 0x0800 != 0 {
+        load
+    } else {
+        store
+    }
+}
+
+pub fn decode(raw: HalfWord) -> Box<Decoder> {
+    let format = match raw {
+        v if (v & 0xF800) == 0x1800 => Format::AddSubtract,
+        v if (v & 0xE000) == 0x0000 => Format::MoveShiftedRegister,
+        v if (v & 0xE000) == 0x2000 => Format::Immediate,
+        v if (v & 0xFC00) == 0x4000 => Format::ALU,
+        v if (v & 0xFC00) == 0x4400 => Format::HiRegister,
+        v if (v & 0xF800) == 0x4800 => Format::PCRelativeLoad,
+        v if (v & 0xF200) == 0x5000 => Format::LoadStoreRegisterOffset,
+        v if (v & 0xF200) == 0x5200 => Format::LoadStoreSignExtended,
+        v if (v & 0xE000) == 0x6000 => Format::LoadStoreImmediateOffset,
+        v if (v & 0xF000) == 0x8000 => Format::LoadStoreHalfWord,
+        v if (v & 0xF000) == 0x9000 => Format::SPRelativeLoadStore,
+        v if (v & 0xF000) == 0xA000 => Format::LoadAddress,
+        v if (v & 0xFF00) == 0xB000 => Format::AddOffsetToSP,
+        v if (v & 0xF600) == 0xB400 => Format::PushPop,
+        v if (v & 0xF000) == 0xC000 => Format::MultiLoadAndStore,
+        v if (v & 0xFF00) == 0xDF00 => Format::SWI,
+        v if (v & 0xFF00) == 0xDE00 => Format::Undefined,
+        v if (v & 0xF000) == 0xD000 => Format::ConditionalBranch,
+        v if (v & 0xF800) == 0xE000 => Format::UnconditionalBranch,
+        v if (v & 0xF000) == 0xF000 => Format::LongBranchWithLink,
+        _ => Format::Undefined,
+    };
+
+    let opcode = match format {
+        Format::MoveShiftedRegister => decode_move_shifted_register(raw),
+        Format::AddSubtract => decode_add_subtract(raw),
+        Format::Immediate => decode_immediate(raw),
+        Format::ALU => decode_alu(raw),
+        Format::HiRegister => decode_hi_register(raw),
+        Format::PCRelativeLoad => Opcode::LDR,
+        Format::LoadStoreRegisterOffset => decode_load_store_register_offset(raw),
+        Format::LoadStoreSignExtended => decode_load_store_sign_extended(raw),
+        Format::LoadStoreImmediateOffset => decode_load_store_immediate_offset(raw),
+        Format::LoadStoreHalfWord => decode_load_or_store(raw, Opcode::LDRH, Opcode::STRH),
+        Format::SPRelativeLoadStore => decode_load_or_store(raw, Opcode::LDR, Opcode::STR),
+        Format::LoadAddress | Format::AddOffsetToSP => Opcode::ADD,
+        Format::PushPop => decode_load_or_store(raw, Opcode::POP, Opcode::PUSH),
+        Format::MultiLoadAndStore => decode_load_or_store(raw, Opcode::LDMIA, Opcode::STMIA),
+        Format::ConditionalBranch | Format::UnconditionalBranch => Opcode::B,
+        Format::SWI => Opcode::SWI,
+        Format::LongBranchWithLink => Opcode::BL,
+        Format::Undefined => Opcode::Undefined,
+    };
+
+    debug!("thumb format = {:?}, opcode = {:?}", format, opcode);
+    let dec = BaseDecoder {
+        raw,
+        format,
+        opcode,
+    };
+    match dec.format {
+        Format::Immediate
+        | Format::PCRelativeLoad
+        | Format::SPRelativeLoadStore
+        | Format::LoadAddress
+        | Format::MultiLoadAndStore => Box::new(ImmediateDecoder(dec)),
+        Format::HiRegister => Box::new(HiRegisterDecoder(dec)),
+        _ => Box::new(dec),
+    }
+}
+
+#[test]
+fn decode_move_shifted_register_lsr() {
+    // lsr r1, r2, #4
+    let dec = decode(0x0911);
+    assert_eq!(dec.format(), Format::MoveShiftedRegister);
+    assert_eq!(dec.opcode(), Opcode::LSR);
+    assert_eq!(dec.get_Rd(), 1);
+    assert_eq!(dec.get_Rs(), 2);
+    assert_eq!(dec.get_offset5(), 4);
+}
+
+#[test]
+fn decode_add_subtract_immediate() {
+    // sub r0, r1, #3
+    let dec = decode(0x1EC8);
+    assert_eq!(dec.format(), Format::AddSubtract);
+    assert_eq!(dec.opcode(), Opcode::SUB);
+    assert_eq!(dec.has_I(), true);
+    assert_eq!(dec.get_offset3(), 3);
+    assert_eq!(dec.get_Rs(), 1);
+    assert_eq!(dec.get_Rd(), 0);
+}
+
+#[test]
+fn decode_immediate_cmp() {
+    // cmp r5, #0xAA
+    let dec = decode(0x2DAA);
+    assert_eq!(dec.format(), Format::Immediate);
+    assert_eq!(dec.opcode(), Opcode::CMP);
+    assert_eq!(dec.get_Rd(), 5);
+    assert_eq!(dec.get_imm8(), 0xAA);
+}
+
+#[test]
+fn decode_alu_mul() {
+    // mul r2, r3
+    let dec = decode(0x435A);
+    assert_eq!(dec.format(), Format::ALU);
+    assert_eq!(dec.opcode(), Opcode::MUL);
+    assert_eq!(dec.get_Rd(), 2);
+    assert_eq!(dec.get_Rs(), 3);
+}
+
+#[test]
+fn decode_hi_register_mov_and_bx() {
+    // mov r8, r1
+    let dec = decode(0x4688);
+    assert_eq!(dec.format(), Format::HiRegister);
+    assert_eq!(dec.opcode(), Opcode::MOV);
+    assert_eq!(dec.get_Rd(), 8);
+    assert_eq!(dec.get_Rs(), 1);
+    // bx lr
+    let dec = decode(0x4770);
+    assert_eq!(dec.opcode(), Opcode::BX);
+    assert_eq!(dec.get_Rs(), 14);
+}
+
+#[test]
+fn decode_pc_relative_load() {
+    // ldr r3, [pc, #0x10]
+    let dec = decode(0x4B04);
+    assert_eq!(dec.format(), Format::PCRelativeLoad);
+    assert_eq!(dec.opcode(), Opcode::LDR);
+    assert_eq!(dec.get_Rd(), 3);
+    assert_eq!(dec.get_imm8(), 4);
+}
+
+#[test]
+fn decode_load_store_with_offsets() {
+    // ldrb r0, [r1, r2]
+    let dec = decode(0x5C88);
+    assert_eq!(dec.format(), Format::LoadStoreRegisterOffset);
+    assert_eq!(dec.opcode(), Opcode::LDRB);
+    assert_eq!(dec.get_Ro(), 2);
+    assert_eq!(dec.get_Rb(), 1);
+    // ldsh r0, [r1, r2]
+    let dec = decode(0x5E88);
+    assert_eq!(dec.format(), Format::LoadStoreSignExtended);
+    assert_eq!(dec.opcode(), Opcode::LDSH);
+    // str r0, [r1, #4]
+    let dec = decode(0x6048);
+    assert_eq!(dec.format(), Format::LoadStoreImmediateOffset);
+    assert_eq!(dec.opcode(), Opcode::STR);
+    assert_eq!(dec.get_offset5(), 1);
+    // ldrh r0, [r1, #2]
+    let dec = decode(0x8848);
+    assert_eq!(dec.format(), Format::LoadStoreHalfWord);
+    assert_eq!(dec.opcode(), Opcode::LDRH);
+    // str r2, [sp, #8]
+    let dec = decode(0x9202);
+    assert_eq!(dec.format(), Format::SPRelativeLoadStore);
+    assert_eq!(dec.opcode(), Opcode::STR);
+    assert_eq!(dec.get_Rd(), 2);
+}
+
+#[test]
+fn decode_sp_arithmetic() {
+    // add r1, sp, #0x20
+    let dec = decode(0xA908);
+    assert_eq!(dec.format(), Format::LoadAddress);
+    assert_eq!(dec.is_sp(), true);
+    assert_eq!(dec.get_Rd(), 1);
+    // sub sp, #0x10
+    let dec = decode(0xB084);
+    assert_eq!(dec.format(), Format::AddOffsetToSP);
+    assert_eq!(dec.get_sp_offset(), -0x10);
+}
+
+#[test]
+fn decode_push_pop_and_multiple() {
+    // push {r4, lr}
+    let dec = decode(0xB510);
+    assert_eq!(dec.format(), Format::PushPop);
+    assert_eq!(dec.opcode(), Opcode::PUSH);
+    assert_eq!(dec.has_R(), true);
+    assert_eq!(dec.get_register_list(), 0x10);
+    // pop {r4, pc}
+    let dec = decode(0xBD10);
+    assert_eq!(dec.opcode(), Opcode::POP);
+    // ldmia r3!, {r0, r1}
+    let dec = decode(0xCB03);
+    assert_eq!(dec.format(), Format::MultiLoadAndStore);
+    assert_eq!(dec.opcode(), Opcode::LDMIA);
+    assert_eq!(dec.get_Rb(), 3);
+}
+
+#[test]
+fn decode_branches() {
+    // bne pc-4
+    let dec = decode(0xD1FC);
+    assert_eq!(dec.format(), Format::ConditionalBranch);
+    assert_eq!(dec.get_cond(), Condition::NE);
+    assert_eq!(dec.get_soffset8(), -8);
+    // swi 0x12
+    let dec = decode(0xDF12);
+    assert_eq!(dec.format(), Format::SWI);
+    assert_eq!(dec.get_imm8(), 0x12);
+    // b pc-4
+    let dec = decode(0xE7FE);
+    assert_eq!(dec.format(), Format::UnconditionalBranch);
+    assert_eq!(dec.get_soffset11(), -4);
+    // bl (second half)
+    let dec = decode(0xF801);
+    assert_eq!(dec.format(), Format::LongBranchWithLink);
+    assert_eq!(dec.is_bl_low(), true);
+    assert_eq!(dec.get_offset11(), 1);
+}
+
+#[test]
+fn decode_undefined() {
+    let dec = decode(0xDE00);
+    assert_eq!(dec.opcode(), Opcode::Undefined);
+}
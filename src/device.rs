@@ -0,0 +1,44 @@
+use bus::BusError;
+use types::Word;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Width {
+    Byte,
+    HalfWord,
+    Word,
+}
+
+impl Width {
+    pub fn bytes(self) -> Word {
+        match self {
+            Width::Byte => 1,
+            Width::HalfWord => 2,
+            Width::Word => 4,
+        }
+    }
+}
+
+// Interrupt line of the core a device output is wired to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Interrupt {
+    IRQ,
+    FIQ,
+}
+
+// Peripheral mapped into the address space with `MemoryMap::map_device`.
+// The core clocks it through the bus and samples its interrupt output every tick.
+pub trait Device {
+    // `offset` is relative to the base the device is mapped at, data is in the low bits.
+    // Reads take &mut self since reading a register may have side effects, e.g. popping a FIFO.
+    // An access the device can not serve is failed with `BusError::Device` and aborts on the core.
+    fn read(&mut self, offset: Word, width: Width) -> Result<Word, BusError>;
+    fn write(&mut self, offset: Word, width: Width, data: Word) -> Result<(), BusError>;
+
+    // Called with the number of core cycles elapsed since the last call.
+    fn tick(&mut self, _cycles: u32) {}
+
+    // Level of the interrupt output, None while it is not asserted.
+    fn interrupt(&self) -> Option<Interrupt> {
+        None
+    }
+}
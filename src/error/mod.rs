@@ -1,15 +1,22 @@
 use std::error;
 use std::fmt;
 
+use bus::BusError;
+use types::Word;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum ArmError {
     UnknownError,
+    // Raised by a load or store with the bus error and the fault address,
+    // the core takes the data abort exception.
+    DataAbort(BusError, Word),
 }
 
 impl error::Error for ArmError {
     fn description(&self) -> &str {
         match *self {
             ArmError::UnknownError => "Unknown ARM error",
+            ArmError::DataAbort(_, _) => "Data abort",
         }
     }
 }
@@ -18,6 +25,7 @@ impl fmt::Display for ArmError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             ArmError::UnknownError => write!(f, "Unknown ARM error"),
+            ArmError::DataAbort(e, addr) => write!(f, "Data abort, {:?} at {:x}", e, addr),
         }
     }
 }
@@ -0,0 +1,77 @@
+use registers::psr::Mode;
+use types::Word;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Exception {
+    Reset,
+    Undefined,
+    SoftwareInterrupt,
+    PrefetchAbort,
+    DataAbort,
+    IRQ,
+    FIQ,
+}
+
+impl Exception {
+    pub fn vector(self) -> Word {
+        match self {
+            Exception::Reset => 0x00,
+            Exception::Undefined => 0x04,
+            Exception::SoftwareInterrupt => 0x08,
+            Exception::PrefetchAbort => 0x0C,
+            Exception::DataAbort => 0x10,
+            Exception::IRQ => 0x18,
+            Exception::FIQ => 0x1C,
+        }
+    }
+
+    pub fn mode(self) -> Mode {
+        match self {
+            Exception::Reset | Exception::SoftwareInterrupt => Mode::Supervisor,
+            Exception::Undefined => Mode::Undefined,
+            Exception::PrefetchAbort | Exception::DataAbort => Mode::Abort,
+            Exception::IRQ => Mode::IRQ,
+            Exception::FIQ => Mode::FIQ,
+        }
+    }
+
+    // 1 is the highest priority.
+    pub fn priority(self) -> u8 {
+        match self {
+            Exception::Reset => 1,
+            Exception::DataAbort => 2,
+            Exception::FIQ => 3,
+            Exception::IRQ => 4,
+            Exception::PrefetchAbort => 5,
+            Exception::Undefined | Exception::SoftwareInterrupt => 6,
+        }
+    }
+
+    pub fn disables_fiq(self) -> bool {
+        self == Exception::Reset || self == Exception::FIQ
+    }
+
+    // Offset from the address of the instruction which caused the exception
+    // (or which was about to be executed for an interrupt) to the value of LR.
+    pub fn return_offset(self, instruction_size: Word) -> Word {
+        match self {
+            Exception::Undefined | Exception::SoftwareInterrupt => instruction_size,
+            Exception::DataAbort => 8,
+            _ => 4,
+        }
+    }
+}
+
+#[test]
+fn data_abort_has_priority_over_fiq() {
+    assert!(Exception::DataAbort.priority() < Exception::FIQ.priority());
+    assert!(Exception::FIQ.priority() < Exception::IRQ.priority());
+    assert!(Exception::IRQ.priority() < Exception::PrefetchAbort.priority());
+}
+
+#[test]
+fn return_offset_in_thumb_state() {
+    assert_eq!(Exception::SoftwareInterrupt.return_offset(2), 2);
+    assert_eq!(Exception::IRQ.return_offset(2), 4);
+    assert_eq!(Exception::DataAbort.return_offset(2), 8);
+}
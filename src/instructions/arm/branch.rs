@@ -2,6 +2,7 @@ use super::super::PipelineStatus;
 use constants::*;
 use decoder::arm::Decoder;
 use error::ArmError;
+use registers::psr::{State, PSR};
 use types::*;
 
 pub fn exec_bl(dec: &Decoder, gpr: &mut [Word; 16]) -> Result<PipelineStatus, ArmError> {
@@ -19,3 +20,40 @@ pub fn exec_b(dec: &Decoder, gpr: &mut [Word; 16]) -> Result<PipelineStatus, Arm
     gpr[PC] = (gpr[PC] as i32 + imm * 4) as Word;
     Ok(PipelineStatus::Flush)
 }
+
+// Bit 0 of Rm selects the state of the target, 1: Thumb 0: ARM.
+pub fn exec_bx(
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    cpsr: &mut PSR,
+) -> Result<PipelineStatus, ArmError> {
+    let rm = gpr[dec.get_Rm()];
+    if rm & 1 != 0 {
+        cpsr.set_state(State::THUMB);
+        gpr[PC] = rm & !1;
+    } else {
+        cpsr.set_state(State::ARM);
+        gpr[PC] = rm & !3;
+    }
+    Ok(PipelineStatus::Flush)
+}
+
+// BLX <label> always switches to Thumb, H (bit 24) is bit 1 of the target address.
+// BLX Rm switches on bit 0 of Rm like BX.
+#[cfg(feature = "armv5te")]
+pub fn exec_blx(
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    cpsr: &mut PSR,
+) -> Result<PipelineStatus, ArmError> {
+    let next = gpr[PC] - 4;
+    if dec.raw() & COND_FIELD == COND_NV {
+        let offset = ((dec.get_imm24() << 8) >> 6) as Word | (dec.raw() >> 23) & 2;
+        gpr[PC] = gpr[PC].wrapping_add(offset);
+        cpsr.set_state(State::THUMB);
+    } else {
+        exec_bx(dec, gpr, cpsr)?;
+    }
+    gpr[LR] = next;
+    Ok(PipelineStatus::Flush)
+}
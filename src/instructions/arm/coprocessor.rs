@@ -0,0 +1,138 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use bus::Bus;
+use constants::*;
+use coprocessor::Coprocessor;
+use decoder::arm::Decoder;
+use error::ArmError;
+use registers::psr::PSR;
+use types::*;
+
+// Each helper returns whether the coprocessor accepted the instruction.
+
+// 31    28 27  25 24  23  22  21  20 19    16 15    12 11    8 7          0
+// --------------------------------------------------------------------------
+// | cond | 1 1 0 | P | U | N | W | L |  Rn  |  CRd  |  CP#  |   Offset   |
+// --------------------------------------------------------------------------
+// Offset is in words.
+fn exec_data_transfer<F>(dec: &Decoder, gpr: &mut [Word; 16], transfer: F) -> Result<bool, ArmError>
+where
+    F: FnOnce(Word) -> Result<bool, ArmError>,
+{
+    let base = gpr[dec.get_Rn()];
+    let offset = dec.get_imm8() << 2;
+    let offset_base = if dec.is_plus_offset() {
+        base.wrapping_add(offset)
+    } else {
+        base.wrapping_sub(offset)
+    };
+    let addr = if dec.is_pre_indexed() {
+        offset_base
+    } else {
+        base
+    };
+    if !transfer(addr)? {
+        return Ok(false);
+    }
+    if dec.is_write_back() {
+        gpr[dec.get_Rn()] = offset_base;
+    }
+    Ok(true)
+}
+
+pub fn exec_ldc<T>(
+    bus: &Rc<RefCell<T>>,
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    cp: &mut Coprocessor,
+) -> Result<bool, ArmError>
+where
+    T: Bus,
+{
+    exec_data_transfer(dec, gpr, |addr| {
+        // INFO: The coprocessor can not see bus errors, the first one aborts the transfer.
+        let mut fault = None;
+        let accepted = cp.ldc(dec.get_Rd(), dec.is_long(), &mut |n| {
+            let addr = addr.wrapping_add(n as Word * 4);
+            bus.borrow().read_word(addr).unwrap_or_else(|e| {
+                fault = fault.or(Some((e, addr)));
+                0
+            })
+        });
+        match fault {
+            Some((e, addr)) => Err(e.data_abort(addr)),
+            None => Ok(accepted),
+        }
+    })
+}
+
+pub fn exec_stc<T>(
+    bus: &Rc<RefCell<T>>,
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    cp: &mut Coprocessor,
+) -> Result<bool, ArmError>
+where
+    T: Bus,
+{
+    exec_data_transfer(dec, gpr, |addr| {
+        let mut fault = None;
+        let accepted = cp.stc(dec.get_Rd(), dec.is_long(), &mut |n, data| {
+            let addr = addr.wrapping_add(n as Word * 4);
+            if let Err(e) = bus.borrow_mut().write_word(addr, data) {
+                fault = fault.or(Some((e, addr)));
+            }
+        });
+        match fault {
+            Some((e, addr)) => Err(e.data_abort(addr)),
+            None => Ok(accepted),
+        }
+    })
+}
+
+pub fn exec_cdp(dec: &Decoder, cp: &mut Coprocessor) -> Result<bool, ArmError> {
+    Ok(cp.cdp(
+        dec.get_cp_opc(),
+        dec.get_Rd(),
+        dec.get_Rn(),
+        dec.get_Rm(),
+        dec.get_cp_info(),
+    ))
+}
+
+pub fn exec_mcr(dec: &Decoder, gpr: &[Word; 16], cp: &mut Coprocessor) -> Result<bool, ArmError> {
+    Ok(cp.mcr(
+        dec.get_cp_opc(),
+        dec.get_Rn(),
+        dec.get_Rm(),
+        dec.get_cp_info(),
+        gpr[dec.get_Rd()],
+    ))
+}
+
+pub fn exec_mrc(
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    cpsr: &mut PSR,
+    cp: &mut Coprocessor,
+) -> Result<bool, ArmError> {
+    let data = match cp.mrc(
+        dec.get_cp_opc(),
+        dec.get_Rn(),
+        dec.get_Rm(),
+        dec.get_cp_info(),
+    ) {
+        Some(data) => data,
+        None => return Ok(false),
+    };
+    if dec.get_Rd() == PC {
+        cpsr.set_N(data & 0x8000_0000 != 0);
+        cpsr.set_Z(data & 0x4000_0000 != 0);
+        cpsr.set_C(data & 0x2000_0000 != 0);
+        cpsr.set_V(data & 0x1000_0000 != 0);
+    } else {
+        gpr[dec.get_Rd()] = data;
+    }
+    Ok(true)
+}
@@ -4,7 +4,7 @@ use std::rc::Rc;
 use super::super::PipelineStatus;
 use error::ArmError;
 
-use super::shift::{is_carry_over, lsl, ror, shift};
+use super::shift::{ror, shift_by_immediate, shift_by_register};
 use bus::Bus;
 use decoder::arm::Decoder;
 use registers::psr::PSR;
@@ -14,30 +14,43 @@ use constants::*;
 pub fn exec_data_processing<F>(
     gpr: &mut [Word; 16],
     dec: &Decoder,
+    cpsr: &mut PSR,
     data_process: &mut F,
 ) -> Result<PipelineStatus, ArmError>
 where
-    F: FnMut(&mut [Word; 16], Word, Option<bool>),
+    F: FnMut(&mut [Word; 16], &mut PSR, Word, Word, Option<bool>),
 {
+    // INFO: PC reads as PC+12 when the shift amount comes from a register.
+    let register_shift = !dec.has_I() && dec.is_reg_offset();
+    let read = |gpr: &[Word; 16], n: usize| {
+        if register_shift && n == PC {
+            gpr[PC].wrapping_add(4)
+        } else {
+            gpr[n]
+        }
+    };
     let (value, carry) = if dec.has_I() {
         let shift_value = dec.get_rot() * 2;
-        (
-            ror(dec.get_imm8(), shift_value),
-            is_carry_over(dec.get_sh(), dec.get_imm8(), shift_value),
-        )
+        let value = ror(dec.get_imm8(), shift_value);
+        // INFO: Rotated immediate carries out bit 31, or keeps C when not rotated.
+        let carry = if shift_value == 0 {
+            None
+        } else {
+            Some(value >> 31 != 0)
+        };
+        (value, carry)
     } else {
-        let rm = dec.get_Rm() as usize;
-        let shift_value = if dec.is_reg_offset() {
-            dec.get_Rs()
+        let rm = read(gpr, dec.get_Rm());
+        let (value, carry) = if register_shift {
+            let amount = gpr[dec.get_Rs() as usize] & 0xFF;
+            shift_by_register(dec.get_sh(), rm, amount, cpsr.get_C())
         } else {
-            dec.get_shamt5()
+            shift_by_immediate(dec.get_sh(), rm, dec.get_shamt5(), cpsr.get_C())
         };
-        (
-            shift(dec.get_sh(), gpr[rm], shift_value),
-            is_carry_over(dec.get_sh(), gpr[rm], shift_value),
-        )
+        (value, Some(carry))
     };
-    data_process(gpr, value, carry);
+    let rn = read(gpr, dec.get_Rn());
+    data_process(gpr, cpsr, rn, value, carry);
     if dec.get_Rd() == PC {
         Ok(PipelineStatus::Flush)
     } else {
@@ -45,55 +58,115 @@ where
     }
 }
 
+// Returns `a + b + carry_in` with its carry out and signed overflow.
+pub fn add_with_carry(a: Word, b: Word, carry_in: bool) -> (Word, bool, bool) {
+    let sum = a as u64 + b as u64 + carry_in as u64;
+    let result = sum as Word;
+    let overflow = (!(a ^ b) & (a ^ result)) >> 31 != 0;
+    (result, sum > 0xFFFF_FFFF, overflow)
+}
+
+// N and Z follow the result, C comes from the barrel shifter and V is kept.
+pub fn set_logical_flags(cspr: &mut PSR, result: Word, carry: Option<bool>) {
+    cspr.set_N(result >> 31 != 0);
+    cspr.set_Z(result == 0);
+    if let Some(c) = carry {
+        cspr.set_C(c);
+    }
+}
+
+pub fn set_arithmetic_flags(cspr: &mut PSR, result: Word, carry: bool, overflow: bool) {
+    cspr.set_N(result >> 31 != 0);
+    cspr.set_Z(result == 0);
+    cspr.set_C(carry);
+    cspr.set_V(overflow);
+}
+
+fn exec_logical<F>(
+    gpr: &mut [Word; 16],
+    dec: &Decoder,
+    cspr: &mut PSR,
+    op: F,
+) -> Result<PipelineStatus, ArmError>
+where
+    F: Fn(Word, Word) -> Word,
+{
+    exec_data_processing(gpr, dec, cspr, &mut |gpr, cspr, rn, value, carry| {
+        let result = op(rn, value);
+        gpr[dec.get_Rd()] = result;
+        if dec.has_S() {
+            set_logical_flags(cspr, result, carry);
+        }
+    })
+}
+
+// `op` receives Rn, the shifter operand and the current C flag, and
+// returns the result with its carry out and overflow.
+fn exec_arithmetic<F>(
+    gpr: &mut [Word; 16],
+    dec: &Decoder,
+    cspr: &mut PSR,
+    op: F,
+) -> Result<PipelineStatus, ArmError>
+where
+    F: Fn(Word, Word, bool) -> (Word, bool, bool),
+{
+    exec_data_processing(gpr, dec, cspr, &mut |gpr, cspr, rn, value, _| {
+        let (result, c, v) = op(rn, value, cspr.get_C());
+        gpr[dec.get_Rd()] = result;
+        if dec.has_S() {
+            set_arithmetic_flags(cspr, result, c, v);
+        }
+    })
+}
+
 pub fn exec_mov<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = value;
-    })
+    exec_logical(gpr, dec, cspr, |_, value| value)
 }
 
 pub fn exec_and<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = gpr[dec.get_Rn()] & value;
-    })
+    exec_logical(gpr, dec, cspr, |rn, value| rn & value)
 }
 
 pub fn exec_eor<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = gpr[dec.get_Rn()] ^ value;
-    })
+    exec_logical(gpr, dec, cspr, |rn, value| rn ^ value)
 }
 
 pub fn exec_sub<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = gpr[dec.get_Rn()].wrapping_sub(value);
+    exec_arithmetic(gpr, dec, cspr, |rn, value, _| {
+        add_with_carry(rn, !value, true)
     })
 }
 
@@ -101,12 +174,13 @@ pub fn exec_rsb<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = value.wrapping_sub(gpr[dec.get_Rn()]);
+    exec_arithmetic(gpr, dec, cspr, |rn, value, _| {
+        add_with_carry(value, !rn, true)
     })
 }
 
@@ -114,12 +188,13 @@ pub fn exec_add<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = gpr[dec.get_Rn()].wrapping_add(value);
+    exec_arithmetic(gpr, dec, cspr, |rn, value, _| {
+        add_with_carry(rn, value, false)
     })
 }
 
@@ -127,48 +202,36 @@ pub fn exec_adc<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
-    cspr: &PSR,
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = gpr[dec.get_Rn()]
-            .wrapping_add(value)
-            .wrapping_add(if cspr.get_C() { 1 } else { 0 });
-    })
+    exec_arithmetic(gpr, dec, cspr, add_with_carry)
 }
 
 pub fn exec_sbc<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
-    cspr: &PSR,
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = gpr[dec.get_Rn()]
-            .wrapping_sub(value)
-            .wrapping_sub(if cspr.get_C() { 0 } else { 1 });
-    })
+    exec_arithmetic(gpr, dec, cspr, |rn, value, c| add_with_carry(rn, !value, c))
 }
 
 pub fn exec_rsc<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
-    cspr: &PSR,
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = gpr[dec.get_Rn()]
-            .wrapping_sub(value)
-            .wrapping_sub(if cspr.get_C() { 0 } else { 1 });
-    })
+    exec_arithmetic(gpr, dec, cspr, |rn, value, c| add_with_carry(value, !rn, c))
 }
 
 pub fn exec_tst<T>(
@@ -180,13 +243,9 @@ pub fn exec_tst<T>(
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, carry| {
-        let tst = gpr[dec.get_Rn()] & value;
-        cspr.set_N(tst >> 31 != 0);
-        cspr.set_Z(tst == 0);
-        if let Some(c) = carry {
-            cspr.set_C(c);
-        }
+    exec_data_processing(gpr, dec, cspr, &mut |_, cspr, rn, value, carry| {
+        let tst = rn & value;
+        set_logical_flags(cspr, tst, carry);
     })
 }
 
@@ -199,13 +258,9 @@ pub fn exec_teq<T>(
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, carry| {
-        let teq = gpr[dec.get_Rn()] ^ value;
-        cspr.set_N(teq >> 31 != 0);
-        cspr.set_Z(teq == 0);
-        if let Some(c) = carry {
-            cspr.set_C(c);
-        }
+    exec_data_processing(gpr, dec, cspr, &mut |_, cspr, rn, value, carry| {
+        let teq = rn ^ value;
+        set_logical_flags(cspr, teq, carry);
     })
 }
 
@@ -218,15 +273,9 @@ pub fn exec_cmp<T>(
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        let rn = gpr[dec.get_Rn()];
-        let cmp = rn.wrapping_sub(value);
-        cspr.set_N(cmp >> 31 != 0);
-        cspr.set_Z(cmp == 0);
-        let (_, v) = (rn as i32).overflowing_sub(value as i32);
-        cspr.set_V(v);
-        // NOTE: Should we consider to shifted carry?
-        cspr.set_C(rn >= value);
+    exec_data_processing(gpr, dec, cspr, &mut |_, cspr, rn, value, _| {
+        let (cmp, c, v) = add_with_carry(rn, !value, true);
+        set_arithmetic_flags(cspr, cmp, c, v);
     })
 }
 
@@ -239,14 +288,9 @@ pub fn exec_cmn<T>(
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        let rn = gpr[dec.get_Rn()];
-        let cmn = (rn as u64).wrapping_add(value as u64);
-        cspr.set_N((cmn as i32) < 0);
-        cspr.set_Z(cmn == 0);
-        let (_, v) = (rn as i32).overflowing_add(value as i32);
-        cspr.set_V(v);
-        cspr.set_C(cmn & (1 << 32) != 0);
+    exec_data_processing(gpr, dec, cspr, &mut |_, cspr, rn, value, _| {
+        let (cmn, c, v) = add_with_carry(rn, value, false);
+        set_arithmetic_flags(cspr, cmn, c, v);
     })
 }
 
@@ -254,62 +298,59 @@ pub fn exec_orr<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = gpr[dec.get_Rn()] | value;
-    })
+    exec_logical(gpr, dec, cspr, |rn, value| rn | value)
 }
 
 pub fn exec_shift<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = value;
-    })
+    exec_logical(gpr, dec, cspr, |_, value| value)
 }
 
 pub fn exec_bic<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = gpr[dec.get_Rn()] & !value
-    })
+    exec_logical(gpr, dec, cspr, |rn, value| rn & !value)
 }
 
 pub fn exec_mvn<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| gpr[dec.get_Rd()] = !value)
+    exec_logical(gpr, dec, cspr, |_, value| !value)
 }
 
 pub fn exec_rrx<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
-    cspr: &PSR,
+    cspr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_data_processing(gpr, dec, &mut |gpr, value, _| {
-        gpr[dec.get_Rd()] = value >> 1 | (if cspr.get_C() { 0x8000_0000 } else { 0 })
-    })
+    // INFO: ROR #0 is shifted as RRX by the barrel shifter.
+    exec_logical(gpr, dec, cspr, |_, value| value)
 }
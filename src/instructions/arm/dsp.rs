@@ -0,0 +1,185 @@
+use std::i32;
+
+use super::super::PipelineStatus;
+use constants::*;
+use decoder::arm::Decoder;
+use error::ArmError;
+use registers::psr::PSR;
+use types::*;
+
+// Enhanced DSP instructions of ARMv5TE and CLZ.
+// Q is set when a result saturates or an accumulation overflows, it is never cleared here.
+
+fn pipeline_status(dec: &Decoder, rd: usize) -> Result<PipelineStatus, ArmError> {
+    if rd == PC {
+        warn!("{:?} with Rd == PC is unpredictable.", dec.opcode());
+        Ok(PipelineStatus::Flush)
+    } else {
+        Ok(PipelineStatus::Continue)
+    }
+}
+
+fn saturate(value: i64) -> (i32, bool) {
+    if value > i32::MAX as i64 {
+        (i32::MAX, true)
+    } else if value < i32::MIN as i64 {
+        (i32::MIN, true)
+    } else {
+        (value as i32, false)
+    }
+}
+
+// Signed 16 bits of `value`, the top halfword if `top`.
+fn halfword(value: Word, top: bool) -> i32 {
+    if top {
+        (value >> 16) as i16 as i32
+    } else {
+        value as i16 as i32
+    }
+}
+
+// 31    28 27                 16 15  12 11          4 3    0
+// ----------------------------------------------------------
+// | cond | 0001 0110 1111 |  Rd  | 1111 0001 |  Rm  |
+// ----------------------------------------------------------
+pub fn exec_clz(dec: &Decoder, gpr: &mut [Word; 16]) -> Result<PipelineStatus, ArmError> {
+    gpr[dec.get_Rd()] = gpr[dec.get_Rm()].leading_zeros();
+    pipeline_status(dec, dec.get_Rd())
+}
+
+// 31    28 27     23 22 21 20 19  16 15  12 11      4 3    0
+// ---------------------------------------------------------
+// | cond | 0 0 0 1 0 | op | 0 |  Rn  |  Rd  | 0000 0101 |  Rm  |
+// ---------------------------------------------------------
+// op = 00: QADD 01: QSUB 10: QDADD 11: QDSUB
+// QDADD and QDSUB saturate Rn * 2 before the addition or subtraction.
+fn exec_saturating<F>(
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    cpsr: &mut PSR,
+    doubling: bool,
+    op: F,
+) -> Result<PipelineStatus, ArmError>
+where
+    F: Fn(i64, i64) -> i64,
+{
+    let rm = gpr[dec.get_Rm()] as i32 as i64;
+    let mut rn = gpr[dec.get_Rn()] as i32 as i64;
+    if doubling {
+        let (doubled, saturated) = saturate(rn * 2);
+        if saturated {
+            cpsr.set_Q();
+        }
+        rn = doubled as i64;
+    }
+    let (result, saturated) = saturate(op(rm, rn));
+    if saturated {
+        cpsr.set_Q();
+    }
+    gpr[dec.get_Rd()] = result as Word;
+    pipeline_status(dec, dec.get_Rd())
+}
+
+pub fn exec_qadd(
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    cpsr: &mut PSR,
+) -> Result<PipelineStatus, ArmError> {
+    exec_saturating(dec, gpr, cpsr, false, |rm, rn| rm + rn)
+}
+
+pub fn exec_qsub(
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    cpsr: &mut PSR,
+) -> Result<PipelineStatus, ArmError> {
+    exec_saturating(dec, gpr, cpsr, false, |rm, rn| rm - rn)
+}
+
+pub fn exec_qdadd(
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    cpsr: &mut PSR,
+) -> Result<PipelineStatus, ArmError> {
+    exec_saturating(dec, gpr, cpsr, true, |rm, rn| rm + rn)
+}
+
+pub fn exec_qdsub(
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    cpsr: &mut PSR,
+) -> Result<PipelineStatus, ArmError> {
+    exec_saturating(dec, gpr, cpsr, true, |rm, rn| rm - rn)
+}
+
+// 31    28 27     23 22 21 20 19  16 15  12 11   8 7 6 5 4 3    0
+// ---------------------------------------------------------------
+// | cond | 0 0 0 1 0 | op | 0 |  Rd  |  Ra  |  Rm  | 1 y x 0 |  Rn  |
+// ---------------------------------------------------------------
+// INFO: Registers are named after the multiply decoder, Rm and Rn are Rs and Rm in the ARM ARM.
+// x selects the halfword of Rn and y the halfword of Rm, 1: Top 0: Bottom.
+pub fn exec_smlaxy(
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    cpsr: &mut PSR,
+) -> Result<PipelineStatus, ArmError> {
+    let product =
+        halfword(gpr[dec.get_Rn()], dec.is_top_x()) * halfword(gpr[dec.get_Rm()], dec.is_top_y());
+    let (result, overflow) = product.overflowing_add(gpr[dec.get_Ra()] as i32);
+    if overflow {
+        cpsr.set_Q();
+    }
+    gpr[dec.get_Rd()] = result as Word;
+    pipeline_status(dec, dec.get_Rd())
+}
+
+pub fn exec_smulxy(dec: &Decoder, gpr: &mut [Word; 16]) -> Result<PipelineStatus, ArmError> {
+    let product =
+        halfword(gpr[dec.get_Rn()], dec.is_top_x()) * halfword(gpr[dec.get_Rm()], dec.is_top_y());
+    gpr[dec.get_Rd()] = product as Word;
+    pipeline_status(dec, dec.get_Rd())
+}
+
+// The top 32 bits of the 48 bit product of Rn and a halfword of Rm.
+fn multiply_word_by_halfword(dec: &Decoder, gpr: &[Word; 16]) -> i32 {
+    let product =
+        gpr[dec.get_Rn()] as i32 as i64 * halfword(gpr[dec.get_Rm()], dec.is_top_y()) as i64;
+    (product >> 16) as i32
+}
+
+pub fn exec_smlawy(
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    cpsr: &mut PSR,
+) -> Result<PipelineStatus, ArmError> {
+    let product = multiply_word_by_halfword(dec, gpr);
+    let (result, overflow) = product.overflowing_add(gpr[dec.get_Ra()] as i32);
+    if overflow {
+        cpsr.set_Q();
+    }
+    gpr[dec.get_Rd()] = result as Word;
+    pipeline_status(dec, dec.get_Rd())
+}
+
+pub fn exec_smulwy(dec: &Decoder, gpr: &mut [Word; 16]) -> Result<PipelineStatus, ArmError> {
+    gpr[dec.get_Rd()] = multiply_word_by_halfword(dec, gpr) as Word;
+    pipeline_status(dec, dec.get_Rd())
+}
+
+// RdHi:RdLo += product, the 64 bit accumulation wraps and does not set Q.
+pub fn exec_smlalxy(dec: &Decoder, gpr: &mut [Word; 16]) -> Result<PipelineStatus, ArmError> {
+    let product =
+        halfword(gpr[dec.get_Rn()], dec.is_top_x()) * halfword(gpr[dec.get_Rm()], dec.is_top_y());
+    let base = ((gpr[dec.get_Rd()] as u64) << 32) | gpr[dec.get_Ra()] as u64;
+    let result = base.wrapping_add(product as i64 as u64);
+    gpr[dec.get_Ra()] = result as Word;
+    gpr[dec.get_Rd()] = (result >> 32) as Word;
+    pipeline_status(dec, dec.get_Rd())
+}
+
+#[test]
+fn saturate_to_signed_32bit() {
+    assert_eq!(saturate(0x8000_0000), (i32::MAX, true));
+    assert_eq!(saturate(-0x8000_0001), (i32::MIN, true));
+    assert_eq!(saturate(-1), (-1, false));
+}
@@ -7,15 +7,18 @@ use decoder::arm;
 use types::*;
 
 use super::super::PipelineStatus;
+use super::memory::check_alignment;
 use error::ArmError;
 
 fn exec_ex_memory_processing<F>(
     gpr: &mut [u32; 16],
     dec: &arm::Decoder,
+    size: Word,
+    alignment_check: bool,
     load_or_store: F,
 ) -> Result<PipelineStatus, ArmError>
 where
-    F: Fn(&mut [u32; 16], u32),
+    F: Fn(&mut [u32; 16], u32) -> Result<(), ArmError>,
 {
     let mut base = gpr[dec.get_Rn()];
     let offset = if dec.has_I() {
@@ -24,14 +27,15 @@ where
         gpr[dec.get_Rm() as usize]
     };
     let offset_base = if dec.is_plus_offset() {
-        (base + offset) as Word
+        base.wrapping_add(offset)
     } else {
-        (base - offset) as Word
+        base.wrapping_sub(offset)
     };
     if dec.is_pre_indexed() {
         base = offset_base;
     }
-    load_or_store(gpr, base);
+    check_alignment(base, size, alignment_check)?;
+    load_or_store(gpr, base)?;
     if !dec.is_pre_indexed() {
         gpr[dec.get_Rn()] = offset_base;
     } else if dec.is_write_back() {
@@ -48,13 +52,16 @@ pub fn exec_strh<T>(
     bus: &Rc<RefCell<T>>,
     dec: &arm::Decoder,
     gpr: &mut [Word; 16],
+    alignment_check: bool,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_ex_memory_processing(gpr, dec, |gpr, base| {
+    // INFO: Unaligned halfword accesses are forced to the aligned address.
+    exec_ex_memory_processing(gpr, dec, 2, alignment_check, |gpr, base| {
         bus.borrow_mut()
-            .write_word(base, gpr[dec.get_Rd()] & 0xFFFF);
+            .write_halfword(base & !1, gpr[dec.get_Rd()] as HalfWord)
+            .map_err(|e| e.data_abort(base))
     })
 }
 
@@ -63,12 +70,18 @@ pub fn exec_ldrh<T>(
     bus: &Rc<RefCell<T>>,
     dec: &arm::Decoder,
     gpr: &mut [Word; 16],
+    alignment_check: bool,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_ex_memory_processing(gpr, dec, |gpr, base| {
-        gpr[dec.get_Rd()] = bus.borrow().read_word(base) & 0xFFFF;
+    exec_ex_memory_processing(gpr, dec, 2, alignment_check, |gpr, base| {
+        let data = bus
+            .borrow()
+            .read_halfword(base & !1)
+            .map_err(|e| e.data_abort(base))?;
+        gpr[dec.get_Rd()] = data as Word;
+        Ok(())
     })
 }
 
@@ -81,8 +94,13 @@ pub fn exec_ldrsb<T>(
 where
     T: Bus,
 {
-    exec_ex_memory_processing(gpr, dec, |gpr, base| {
-        gpr[dec.get_Rd()] = bus.borrow().read_byte(base) as i8 as i32 as u32;
+    exec_ex_memory_processing(gpr, dec, 1, false, |gpr, base| {
+        let data = bus
+            .borrow()
+            .read_byte(base)
+            .map_err(|e| e.data_abort(base))?;
+        gpr[dec.get_Rd()] = data as i8 as i32 as u32;
+        Ok(())
     })
 }
 
@@ -91,12 +109,79 @@ pub fn exec_ldrsh<T>(
     bus: &Rc<RefCell<T>>,
     dec: &arm::Decoder,
     gpr: &mut [Word; 16],
+    alignment_check: bool,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_ex_memory_processing(gpr, dec, |gpr, base| {
-        gpr[dec.get_Rd()] = (bus.borrow().read_word(base) & 0xFFFF) as i16 as i32 as u32;
+    exec_ex_memory_processing(gpr, dec, 2, alignment_check, |gpr, base| {
+        let data = bus
+            .borrow()
+            .read_halfword(base & !1)
+            .map_err(|e| e.data_abort(base))?;
+        gpr[dec.get_Rd()] = data as i16 as i32 as u32;
+        Ok(())
     })
 }
 
+// Rd must be even, Rd and Rd + 1 are transferred from or to two consecutive words.
+#[cfg(feature = "armv5te")]
+fn exec_doubleword<F>(
+    gpr: &mut [u32; 16],
+    dec: &arm::Decoder,
+    alignment_check: bool,
+    load_or_store: F,
+) -> Result<PipelineStatus, ArmError>
+where
+    F: Fn(&mut [u32; 16], usize, u32) -> Result<(), ArmError>,
+{
+    let rd = dec.get_Rd();
+    if rd & 1 != 0 || rd == LR {
+        warn!("{:?} with R{} is unpredictable.", dec.opcode(), rd);
+    }
+    // INFO: The address is forced to a word boundary, the check is for a doubleword.
+    exec_ex_memory_processing(gpr, dec, 8, alignment_check, |gpr, base| {
+        load_or_store(gpr, rd & !1, base & !3)
+    })
+}
+
+#[cfg(feature = "armv5te")]
+pub fn exec_ldrd<T>(
+    bus: &Rc<RefCell<T>>,
+    dec: &arm::Decoder,
+    gpr: &mut [Word; 16],
+    alignment_check: bool,
+) -> Result<PipelineStatus, ArmError>
+where
+    T: Bus,
+{
+    exec_doubleword(gpr, dec, alignment_check, |gpr, rd, base| {
+        let bus = bus.borrow();
+        let next = base.wrapping_add(4);
+        let low = bus.read_word(base).map_err(|e| e.data_abort(base))?;
+        let high = bus.read_word(next).map_err(|e| e.data_abort(next))?;
+        gpr[rd] = low;
+        gpr[rd + 1] = high;
+        Ok(())
+    })
+}
+
+#[cfg(feature = "armv5te")]
+pub fn exec_strd<T>(
+    bus: &Rc<RefCell<T>>,
+    dec: &arm::Decoder,
+    gpr: &mut [Word; 16],
+    alignment_check: bool,
+) -> Result<PipelineStatus, ArmError>
+where
+    T: Bus,
+{
+    exec_doubleword(gpr, dec, alignment_check, |gpr, rd, base| {
+        let mut bus = bus.borrow_mut();
+        let next = base.wrapping_add(4);
+        bus.write_word(base, gpr[rd])
+            .map_err(|e| e.data_abort(base))?;
+        bus.write_word(next, gpr[rd + 1])
+            .map_err(|e| e.data_abort(next))
+    })
+}
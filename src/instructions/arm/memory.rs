@@ -1,22 +1,45 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use bus::Bus;
+use bus::{Bus, BusError};
 use constants::*;
 use decoder::arm::Decoder;
+use registers::psr::PSR;
 use types::*;
 
 use super::super::PipelineStatus;
-use super::shift::shift;
+use super::shift::{ror, shift_by_immediate};
 use error::ArmError;
 
+// Aborts an access which is not aligned to `size` when alignment checking is enabled.
+pub fn check_alignment(addr: Word, size: Word, enabled: bool) -> Result<(), ArmError> {
+    if enabled && addr & (size - 1) != 0 {
+        Err(BusError::Alignment.data_abort(addr))
+    } else {
+        Ok(())
+    }
+}
+
+// A word read from an unaligned address returns the aligned word rotated
+// so that the addressed byte is the least significant one.
+pub fn read_rotated_word<T>(bus: &T, addr: Word) -> Result<Word, ArmError>
+where
+    T: Bus,
+{
+    let data = bus.read_word(addr & !3).map_err(|e| e.data_abort(addr))?;
+    Ok(ror(data, (addr & 3) * 8))
+}
+
 fn exec_memory_processing<F>(
     gpr: &mut [u32; 16],
     dec: &Decoder,
+    cpsr: &PSR,
+    size: Word,
+    alignment_check: bool,
     load_or_store: F,
 ) -> Result<PipelineStatus, ArmError>
 where
-    F: Fn(&mut [u32; 16], u32),
+    F: Fn(&mut [u32; 16], u32) -> Result<(), ArmError>,
 {
     let mut base = gpr[dec.get_Rn()];
     // INFO: Treat as imm12 if not I.
@@ -26,17 +49,18 @@ where
         let rm = dec.get_Rm() as usize;
         let sh = dec.get_sh();
         let shamt5 = dec.get_shamt5();
-        shift(sh, gpr[rm], shamt5)
+        shift_by_immediate(sh, gpr[rm], shamt5, cpsr.get_C()).0
     };
     let offset_base = if dec.is_plus_offset() {
-        (base + offset) as Word
+        base.wrapping_add(offset)
     } else {
-        (base - offset) as Word
+        base.wrapping_sub(offset)
     };
     if dec.is_pre_indexed() {
         base = offset_base;
     }
-    load_or_store(gpr, base);
+    check_alignment(base, size, alignment_check)?;
+    load_or_store(gpr, base)?;
     if !dec.is_pre_indexed() {
         gpr[dec.get_Rn()] = offset_base;
     } else if dec.is_write_back() {
@@ -54,12 +78,15 @@ pub fn exec_ldr<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cpsr: &PSR,
+    alignment_check: bool,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_memory_processing(gpr, dec, |gpr, base| {
-        gpr[dec.get_Rd()] = bus.borrow().read_word(base);
+    exec_memory_processing(gpr, dec, cpsr, 4, alignment_check, |gpr, base| {
+        gpr[dec.get_Rd()] = read_rotated_word(&*bus.borrow(), base)?;
+        Ok(())
     })
 }
 
@@ -68,12 +95,18 @@ pub fn exec_ldrb<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cpsr: &PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_memory_processing(gpr, dec, |gpr, base| {
-        gpr[dec.get_Rd()] = bus.borrow().read_byte(base) as Word;
+    exec_memory_processing(gpr, dec, cpsr, 1, false, |gpr, base| {
+        let data = bus
+            .borrow()
+            .read_byte(base)
+            .map_err(|e| e.data_abort(base))?;
+        gpr[dec.get_Rd()] = data as Word;
+        Ok(())
     })
 }
 
@@ -81,12 +114,17 @@ pub fn exec_str<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cpsr: &PSR,
+    alignment_check: bool,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_memory_processing(gpr, dec, |gpr, base| {
-        bus.borrow_mut().write_word(base, gpr[dec.get_Rd()]);
+    // INFO: Unaligned stores are forced to the aligned address.
+    exec_memory_processing(gpr, dec, cpsr, 4, alignment_check, |gpr, base| {
+        bus.borrow_mut()
+            .write_word(base & !3, gpr[dec.get_Rd()])
+            .map_err(|e| e.data_abort(base))
     })
 }
 
@@ -94,11 +132,14 @@ pub fn exec_strb<T>(
     bus: &Rc<RefCell<T>>,
     dec: &Decoder,
     gpr: &mut [Word; 16],
+    cpsr: &PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_memory_processing(gpr, dec, |gpr, base| {
-        bus.borrow_mut().write_byte(base, gpr[dec.get_Rd()] as Byte);
+    exec_memory_processing(gpr, dec, cpsr, 1, false, |gpr, base| {
+        bus.borrow_mut()
+            .write_byte(base, gpr[dec.get_Rd()] as Byte)
+            .map_err(|e| e.data_abort(base))
     })
 }
@@ -4,6 +4,11 @@ pub mod branch;
 pub mod data;
 pub mod multiple;
 pub mod multi_load_and_store;
+pub mod psr_transfer;
+pub mod swap;
+pub mod coprocessor;
+#[cfg(feature = "armv5te")]
+pub mod dsp;
 
 mod shift;
 
@@ -4,23 +4,39 @@ use std::rc::Rc;
 use super::super::PipelineStatus;
 use error::ArmError;
 
-use super::data::*;
-use super::shift::{is_carry_over, lsl, ror, shift};
 use bus::Bus;
 use constants::*;
 use decoder::arm;
 use registers::psr::PSR;
 use types::*;
 
+// 31    28 27    23 22  21  20 19  16 15  12 11   8 7    4 3    0
+// ----------------------------------------------------------------
+// | cond | 0 0 0 0 | L | A | S |  Rd  |  Ra  |  Rm  | 1001 |  Rn  |
+// ----------------------------------------------------------------
+// INFO: Rm and Rn are named after the decoder, they are Rs and Rm in the ARM ARM.
+// For long multiplies Rd is RdHi and Ra is RdLo.
+// S = 1: N and Z are set from the result, C and V are left unchanged.
 pub fn exec_multiple<F>(
     gpr: &mut [Word; 16],
     dec: &arm::Decoder,
+    cpsr: &mut PSR,
+    long: bool,
     multiple: &mut F,
 ) -> Result<PipelineStatus, ArmError>
 where
-    F: FnMut(&mut [Word; 16]),
+    F: FnMut(&mut [Word; 16]) -> u64,
 {
-    multiple(gpr);
+    let result = multiple(gpr);
+    if dec.has_S() {
+        if long {
+            cpsr.set_N(result & 0x8000_0000_0000_0000 != 0);
+            cpsr.set_Z(result == 0);
+        } else {
+            cpsr.set_N(result & 0x8000_0000 != 0);
+            cpsr.set_Z(result as u32 == 0);
+        }
+    }
     if dec.get_Rd() == PC {
         Ok(PipelineStatus::Flush)
     } else {
@@ -28,17 +44,78 @@ where
     }
 }
 
+// Operands which make the result of a multiply UNPREDICTABLE.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Unpredictable {
+    PcOperand,
+    // Rd and Rm are the same register.
+    DestinationIsOperand,
+    // RdLo, RdHi and Rm of a long multiply are not distinct.
+    DestinationsOverlap,
+}
+
+// Returns the first UNPREDICTABLE use of operands found, the multiply is executed anyway.
+pub fn check_multiply_operands(dec: &arm::Decoder) -> Option<Unpredictable> {
+    let long = match dec.opcode() {
+        arm::Opcode::MUL | arm::Opcode::MLA => false,
+        _ => true,
+    };
+    let uses_ra = long || dec.opcode() == arm::Opcode::MLA;
+    let unpredictable = if dec.get_Rd() == PC
+        || dec.get_Rn() == PC
+        || dec.get_Rm() == PC
+        || (uses_ra && dec.get_Ra() == PC)
+    {
+        Unpredictable::PcOperand
+    } else if dec.get_Rd() == dec.get_Rn() {
+        Unpredictable::DestinationIsOperand
+    } else if long && (dec.get_Ra() == dec.get_Rd() || dec.get_Ra() == dec.get_Rn()) {
+        Unpredictable::DestinationsOverlap
+    } else {
+        return None;
+    };
+    warn!("{:?} is unpredictable, {:?}.", dec.opcode(), unpredictable);
+    Some(unpredictable)
+}
+
+// Internal cycles of a multiply on the ARM7TDMI. The multiplier terminates early
+// once the remaining bits of the multiplier operand (Rs) are all zeros, or all
+// ones for signed multiplies.
+pub fn multiply_internal_cycles(opcode: &arm::Opcode, rs: Word) -> u32 {
+    let signed = match *opcode {
+        arm::Opcode::UMULL | arm::Opcode::UMLAL => false,
+        _ => true,
+    };
+    let terminates = |mask: Word| rs & mask == 0 || (signed && rs & mask == mask);
+    let m = if terminates(0xFFFF_FF00) {
+        1
+    } else if terminates(0xFFFF_0000) {
+        2
+    } else if terminates(0xFF00_0000) {
+        3
+    } else {
+        4
+    };
+    match *opcode {
+        arm::Opcode::MUL => m,
+        arm::Opcode::MLA | arm::Opcode::UMULL | arm::Opcode::SMULL => m + 1,
+        _ => m + 2,
+    }
+}
+
 pub fn exec_mul<T>(
     bus: &Rc<RefCell<T>>,
     dec: &arm::Decoder,
     gpr: &mut [Word; 16],
-    cspr: &PSR,
+    cpsr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_multiple(gpr, dec, &mut |gpr| {
-        gpr[dec.get_Rd()] = ((gpr[dec.get_Rn()] as u64) * gpr[dec.get_Rm()] as u64) as u32;
+    exec_multiple(gpr, dec, cpsr, false, &mut |gpr| {
+        let result = gpr[dec.get_Rn()].wrapping_mul(gpr[dec.get_Rm()]);
+        gpr[dec.get_Rd()] = result;
+        result as u64
     })
 }
 
@@ -46,14 +123,17 @@ pub fn exec_mla<T>(
     bus: &Rc<RefCell<T>>,
     dec: &arm::Decoder,
     gpr: &mut [Word; 16],
-    cspr: &PSR,
+    cpsr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_multiple(gpr, dec, &mut |gpr| {
-        gpr[dec.get_Rd()] = (((gpr[dec.get_Rn()] as u64) * gpr[dec.get_Rm()] as u64)
-            + gpr[dec.get_Ra()] as u64) as u32;
+    exec_multiple(gpr, dec, cpsr, false, &mut |gpr| {
+        let result = gpr[dec.get_Rn()]
+            .wrapping_mul(gpr[dec.get_Rm()])
+            .wrapping_add(gpr[dec.get_Ra()]);
+        gpr[dec.get_Rd()] = result;
+        result as u64
     })
 }
 
@@ -61,15 +141,16 @@ pub fn exec_umull<T>(
     bus: &Rc<RefCell<T>>,
     dec: &arm::Decoder,
     gpr: &mut [Word; 16],
-    cspr: &PSR,
+    cpsr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_multiple(gpr, dec, &mut |gpr| {
+    exec_multiple(gpr, dec, cpsr, true, &mut |gpr| {
         let mul = (gpr[dec.get_Rn()] as u64) * gpr[dec.get_Rm()] as u64;
         gpr[dec.get_Ra()] = mul as u32;
         gpr[dec.get_Rd()] = (mul >> 32) as u32;
+        mul
     })
 }
 
@@ -77,17 +158,18 @@ pub fn exec_umlal<T>(
     bus: &Rc<RefCell<T>>,
     dec: &arm::Decoder,
     gpr: &mut [Word; 16],
-    cspr: &PSR,
+    cpsr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_multiple(gpr, dec, &mut |gpr| {
+    exec_multiple(gpr, dec, cpsr, true, &mut |gpr| {
         let mul = (gpr[dec.get_Rn()] as u64) * gpr[dec.get_Rm()] as u64;
         let base = ((gpr[dec.get_Rd()] as u64) << 32) + (gpr[dec.get_Ra()] as u64);
-        let result = mul + base;
+        let result = mul.wrapping_add(base);
         gpr[dec.get_Ra()] = result as u32;
         gpr[dec.get_Rd()] = (result >> 32) as u32;
+        result
     })
 }
 
@@ -95,15 +177,16 @@ pub fn exec_smull<T>(
     bus: &Rc<RefCell<T>>,
     dec: &arm::Decoder,
     gpr: &mut [Word; 16],
-    cspr: &PSR,
+    cpsr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_multiple(gpr, dec, &mut |gpr| {
+    exec_multiple(gpr, dec, cpsr, true, &mut |gpr| {
         let mul = (gpr[dec.get_Rn()] as i32 as i64) * gpr[dec.get_Rm()] as i32 as i64;
         gpr[dec.get_Ra()] = mul as u32;
         gpr[dec.get_Rd()] = (mul >> 32) as u32;
+        mul as u64
     })
 }
 
@@ -111,16 +194,36 @@ pub fn exec_smlal<T>(
     bus: &Rc<RefCell<T>>,
     dec: &arm::Decoder,
     gpr: &mut [Word; 16],
-    cspr: &PSR,
+    cpsr: &mut PSR,
 ) -> Result<PipelineStatus, ArmError>
 where
     T: Bus,
 {
-    exec_multiple(gpr, dec, &mut |gpr| {
+    exec_multiple(gpr, dec, cpsr, true, &mut |gpr| {
         let mul = (gpr[dec.get_Rn()] as i32 as i64) * gpr[dec.get_Rm()] as i32 as i64;
         let base = (((gpr[dec.get_Rd()] as u64) << 32) + (gpr[dec.get_Ra()] as u64)) as i64;
-        let result = mul + base;
+        let result = mul.wrapping_add(base);
         gpr[dec.get_Ra()] = result as u32;
         gpr[dec.get_Rd()] = (result >> 32) as u32;
+        result as u64
     })
 }
+
+#[test]
+fn multiply_terminates_early() {
+    assert_eq!(multiply_internal_cycles(&arm::Opcode::MUL, 0x0000_00FF), 1);
+    assert_eq!(multiply_internal_cycles(&arm::Opcode::MUL, 0xFFFF_FF80), 1);
+    assert_eq!(multiply_internal_cycles(&arm::Opcode::MLA, 0x0000_FFFF), 3);
+    assert_eq!(
+        multiply_internal_cycles(&arm::Opcode::SMULL, 0x00FF_FFFF),
+        4
+    );
+    assert_eq!(
+        multiply_internal_cycles(&arm::Opcode::UMULL, 0xFFFF_FF80),
+        5
+    );
+    assert_eq!(
+        multiply_internal_cycles(&arm::Opcode::SMLAL, 0x1234_5678),
+        6
+    );
+}
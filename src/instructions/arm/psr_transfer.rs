@@ -0,0 +1,60 @@
+use super::super::PipelineStatus;
+use error::ArmError;
+
+use super::shift::ror;
+use decoder::arm::Decoder;
+use registers::psr::{Mode, PSR};
+use types::*;
+
+// 31    28 27    23  22  21 20 19  16 15  12 11                       0
+// ---------------------------------------------------------------------
+// | cond | 0 0 0 1 0 | R | 0 0 | 1111 |  Rd  |       0000 0000 0000    | MRS
+// | cond | 0 0 I 1 0 | R | 1 0 | mask | 1111 |   rot, imm8 or 0..0 Rm  | MSR
+// ---------------------------------------------------------------------
+// R = 0: CPSR 1: SPSR
+// mask = <f|s|x|c>, each bit selects one byte of the PSR.
+pub fn exec_mrs(dec: &Decoder, gpr: &mut [Word; 16], psr: PSR) -> Result<PipelineStatus, ArmError> {
+    gpr[dec.get_Rd()] = psr.0;
+    Ok(PipelineStatus::Continue)
+}
+
+fn field_mask(dec: &Decoder) -> Word {
+    let fields = dec.get_field_mask();
+    (0..4).fold(0, |mask, i| {
+        if fields & (1 << i) != 0 {
+            mask | (0xFF << (i * 8))
+        } else {
+            mask
+        }
+    })
+}
+
+// User mode can only write the condition flags of CPSR, and the T bit
+// is never written by MSR on ARMv4T.
+pub fn exec_msr(
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    psr: &mut PSR,
+    is_cpsr: bool,
+    privileged: bool,
+) -> Result<PipelineStatus, ArmError> {
+    let operand = if dec.has_I() {
+        ror(dec.get_imm8(), dec.get_rot() * 2)
+    } else {
+        gpr[dec.get_Rm()]
+    };
+    let mut mask = field_mask(dec);
+    if is_cpsr {
+        if !privileged {
+            mask &= PSR::FLAGS_MASK;
+        }
+        mask &= !(1 << PSR::STATE_BIT);
+    }
+    let value = (psr.0 & !mask) | (operand & mask);
+    if Mode::from_bits(value).is_none() {
+        warn!("MSR with illegal mode bits {:#010b} ignored.", value & 0x1F);
+        return Ok(PipelineStatus::Continue);
+    }
+    psr.set(value);
+    Ok(PipelineStatus::Continue)
+}
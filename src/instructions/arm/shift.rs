@@ -1,25 +1,55 @@
 use types::*;
 
-pub fn shift(shift_type: Shift, value: u32, shift: u32) -> u32 {
+// Shift by the 5 bit immediate of the instruction, LSR #0 and ASR #0 encode #32
+// and ROR #0 encodes RRX. Returns the shifter operand and the shifter carry out.
+pub fn shift_by_immediate(shift_type: Shift, value: u32, amount: u32, carry: bool) -> (u32, bool) {
+    if amount != 0 {
+        return shift_with_carry(shift_type, value, amount);
+    }
     match shift_type {
-        Shift::LSL => lsl(value, shift),
-        Shift::LSR => lsr(value, shift),
-        Shift::ASR => asr(value, shift),
-        Shift::ROR => ror(value, shift),
+        Shift::LSL => (value, carry),
+        Shift::ROR => (rrx(value, carry), value & 1 != 0),
+        _ => shift_with_carry(shift_type, value, 32),
     }
 }
 
-pub fn is_carry_over(shift_type: Shift, value: u32, shift: u32) -> Option<bool> {
-    if shift == 0 {
-        None
+// Shift by the bottom byte of Rs, an amount of 0 keeps the value and the carry.
+pub fn shift_by_register(shift_type: Shift, value: u32, amount: u32, carry: bool) -> (u32, bool) {
+    if amount == 0 {
+        (value, carry)
     } else {
-        match shift_type {
-            Shift::LSL => Some(value & (1 << (32 - shift)) != 0),
-            _ => Some(value & (1 << (shift - 1)) != 0),
-        }
+        shift_with_carry(shift_type, value, amount)
+    }
+}
+
+// `amount` is between 1 and 255.
+fn shift_with_carry(shift_type: Shift, value: u32, amount: u32) -> (u32, bool) {
+    match shift_type {
+        Shift::LSL => match amount {
+            1..=31 => (lsl(value, amount), (value >> (32 - amount)) & 1 != 0),
+            32 => (0, value & 1 != 0),
+            _ => (0, false),
+        },
+        Shift::LSR => match amount {
+            1..=31 => (lsr(value, amount), (value >> (amount - 1)) & 1 != 0),
+            32 => (0, value >> 31 != 0),
+            _ => (0, false),
+        },
+        Shift::ASR => match amount {
+            1..=31 => (asr(value, amount), (value >> (amount - 1)) & 1 != 0),
+            _ => (asr(value, 31), value >> 31 != 0),
+        },
+        Shift::ROR => match amount & 31 {
+            0 => (value, value >> 31 != 0),
+            n => (ror(value, n), (value >> (n - 1)) & 1 != 0),
+        },
     }
 }
 
+pub fn rrx(value: u32, carry: bool) -> u32 {
+    value >> 1 | (carry as u32) << 31
+}
+
 pub fn lsl(value: u32, shift: u32) -> u32 {
     if shift == 0 {
         return value;
@@ -64,20 +94,112 @@ fn test_asr() {
 
 #[test]
 fn test_carry_lsl() {
-    assert_eq!(is_carry_over(Shift::LSL, 0x8000_0000, 1), Some(true));
+    assert_eq!(
+        shift_by_immediate(Shift::LSL, 0x8000_0000, 1, false),
+        (0, true)
+    );
 }
 
 #[test]
 fn test_without_carry_lsl() {
-    assert_eq!(is_carry_over(Shift::LSL, 0x8000_0000, 2), Some(false));
+    assert_eq!(
+        shift_by_immediate(Shift::LSL, 0x8000_0000, 2, true),
+        (0, false)
+    );
 }
 
 #[test]
 fn test_carry_ror() {
-    assert_eq!(is_carry_over(Shift::ROR, 0x0000_0001, 1), Some(true));
+    assert_eq!(
+        shift_by_immediate(Shift::ROR, 0x0000_0001, 1, false),
+        (0x8000_0000, true)
+    );
 }
 
 #[test]
 fn test_without_carry_ror() {
-    assert_eq!(is_carry_over(Shift::ROR, 0x0000_0001, 2), Some(false));
+    assert_eq!(
+        shift_by_immediate(Shift::ROR, 0x0000_0001, 2, true),
+        (0x4000_0000, false)
+    );
+}
+
+#[test]
+fn test_immediate_lsr_and_asr_0_are_32() {
+    assert_eq!(
+        shift_by_immediate(Shift::LSR, 0x8000_0001, 0, false),
+        (0, true)
+    );
+    assert_eq!(
+        shift_by_immediate(Shift::ASR, 0x8000_0001, 0, false),
+        (0xFFFF_FFFF, true)
+    );
+    assert_eq!(
+        shift_by_immediate(Shift::ASR, 0x7FFF_FFFF, 0, true),
+        (0, false)
+    );
+}
+
+#[test]
+fn test_immediate_ror_0_is_rrx() {
+    assert_eq!(
+        shift_by_immediate(Shift::ROR, 0x0000_0003, 0, true),
+        (0x8000_0001, true)
+    );
+    assert_eq!(
+        shift_by_immediate(Shift::ROR, 0x0000_0002, 0, false),
+        (0x0000_0001, false)
+    );
+}
+
+#[test]
+fn test_immediate_lsl_0_keeps_carry() {
+    assert_eq!(
+        shift_by_immediate(Shift::LSL, 0x8000_0000, 0, true),
+        (0x8000_0000, true)
+    );
+}
+
+#[test]
+fn test_register_shift_0_keeps_value_and_carry() {
+    assert_eq!(
+        shift_by_register(Shift::LSR, 0x8000_0000, 0, true),
+        (0x8000_0000, true)
+    );
+    assert_eq!(
+        shift_by_register(Shift::ROR, 0x8000_0000, 0, false),
+        (0x8000_0000, false)
+    );
+}
+
+#[test]
+fn test_register_shift_32_and_more() {
+    assert_eq!(
+        shift_by_register(Shift::LSL, 0x0000_0001, 32, false),
+        (0, true)
+    );
+    assert_eq!(
+        shift_by_register(Shift::LSL, 0x0000_0001, 33, true),
+        (0, false)
+    );
+    assert_eq!(
+        shift_by_register(Shift::LSR, 0x8000_0000, 32, false),
+        (0, true)
+    );
+    assert_eq!(
+        shift_by_register(Shift::LSR, 0x8000_0000, 33, true),
+        (0, false)
+    );
+    assert_eq!(
+        shift_by_register(Shift::ASR, 0x8000_0000, 200, false),
+        (0xFFFF_FFFF, true)
+    );
+    assert_eq!(
+        shift_by_register(Shift::ROR, 0x8000_0001, 32, false),
+        (0x8000_0001, true)
+    );
+    assert_eq!(
+        shift_by_register(Shift::ROR, 0x0000_0001, 33, false),
+        (0x8000_0000, true)
+    );
 }
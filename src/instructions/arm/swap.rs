@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use bus::{Bus, BusError};
+use decoder::arm::Decoder;
+use types::*;
+
+use super::super::PipelineStatus;
+use super::memory::check_alignment;
+use super::shift::ror;
+use error::ArmError;
+
+// 31    28 27      23  22  21 20 19  16 15  12 11      4 3    0
+// --------------------------------------------------------------
+// | cond | 0 0 0 1 0 | B | 0 0 |  Rn  |  Rd  | 0000 1001 |  Rm  |
+// --------------------------------------------------------------
+// B = 0: Word 1: Byte
+// Rd <- [Rn], [Rn] <- Rm with the bus locked between the two accesses.
+fn exec_swap<T, F>(
+    bus: &Rc<RefCell<T>>,
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    swap: F,
+) -> Result<PipelineStatus, ArmError>
+where
+    T: Bus,
+    F: Fn(&mut T, Word, Word) -> Result<Word, BusError>,
+{
+    let addr = gpr[dec.get_Rn()];
+    let mut bus = bus.borrow_mut();
+    bus.lock();
+    let data = swap(&mut *bus, addr, gpr[dec.get_Rm()]);
+    bus.unlock();
+    gpr[dec.get_Rd()] = data.map_err(|e| e.data_abort(addr))?;
+    Ok(PipelineStatus::Continue)
+}
+
+// The word is read like LDR and written like STR from an unaligned address.
+pub fn exec_swp<T>(
+    bus: &Rc<RefCell<T>>,
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+    alignment_check: bool,
+) -> Result<PipelineStatus, ArmError>
+where
+    T: Bus,
+{
+    check_alignment(gpr[dec.get_Rn()], 4, alignment_check)?;
+    exec_swap(bus, dec, gpr, |bus, addr, data| {
+        let read = ror(bus.read_word(addr & !3)?, (addr & 3) * 8);
+        bus.write_word(addr & !3, data)?;
+        Ok(read)
+    })
+}
+
+pub fn exec_swpb<T>(
+    bus: &Rc<RefCell<T>>,
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+) -> Result<PipelineStatus, ArmError>
+where
+    T: Bus,
+{
+    exec_swap(bus, dec, gpr, |bus, addr, data| {
+        let read = bus.read_byte(addr)?;
+        bus.write_byte(addr, data as Byte)?;
+        Ok(read as Word)
+    })
+}
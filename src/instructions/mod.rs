@@ -1,4 +1,5 @@
 pub mod arm;
+pub mod thumb;
 
 #[derive(Debug)]
 pub enum PipelineStatus {
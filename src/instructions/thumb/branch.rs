@@ -0,0 +1,25 @@
+use super::super::PipelineStatus;
+use constants::*;
+use decoder::thumb::Decoder;
+use error::ArmError;
+use types::*;
+
+// Offset of B and B<cond>, already sign extended and converted to bytes.
+pub fn exec_thumb_b(gpr: &mut [Word; 16], offset: i32) -> Result<PipelineStatus, ArmError> {
+    gpr[PC] = gpr[PC].wrapping_add(offset as Word);
+    Ok(PipelineStatus::Flush)
+}
+
+// BL is a pair of instructions, the first one adds the high part of the offset
+// to PC and keeps it in LR, the second one adds the low part and branches.
+pub fn exec_thumb_bl(dec: &Decoder, gpr: &mut [Word; 16]) -> Result<PipelineStatus, ArmError> {
+    if !dec.is_bl_low() {
+        let high = (dec.get_offset11() as i32) << 21 >> 9;
+        gpr[LR] = gpr[PC].wrapping_add(high as Word);
+        return Ok(PipelineStatus::Continue);
+    }
+    let next = gpr[PC].wrapping_sub(2);
+    gpr[PC] = gpr[LR].wrapping_add(dec.get_offset11() << 1);
+    gpr[LR] = next | 1;
+    Ok(PipelineStatus::Flush)
+}
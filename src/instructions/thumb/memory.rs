@@ -0,0 +1,32 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::super::PipelineStatus;
+use bus::Bus;
+use constants::*;
+use decoder::thumb::Decoder;
+use error::ArmError;
+use types::*;
+
+// ldr Rd, [PC, #imm], bit 1 of PC is forced to 0 so the address is word aligned.
+pub fn exec_pc_relative_load<T>(
+    bus: &Rc<RefCell<T>>,
+    dec: &Decoder,
+    gpr: &mut [Word; 16],
+) -> Result<PipelineStatus, ArmError>
+where
+    T: Bus,
+{
+    let addr = (gpr[PC] & !2).wrapping_add(dec.get_imm8() << 2);
+    gpr[dec.get_Rd()] = bus
+        .borrow()
+        .read_word(addr)
+        .map_err(|e| e.data_abort(addr))?;
+    Ok(PipelineStatus::Continue)
+}
+
+// add Rd, PC, #imm, PC is word aligned in the same way as the load.
+pub fn exec_load_address(dec: &Decoder, gpr: &mut [Word; 16]) -> Result<PipelineStatus, ArmError> {
+    gpr[dec.get_Rd()] = (gpr[PC] & !2).wrapping_add(dec.get_imm8() << 2);
+    Ok(PipelineStatus::Continue)
+}
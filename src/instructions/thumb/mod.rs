@@ -0,0 +1,3 @@
+pub mod branch;
+pub mod memory;
+pub mod translate;
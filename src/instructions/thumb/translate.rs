@@ -0,0 +1,244 @@
+use constants::*;
+use decoder::thumb::{Decoder, Format, Opcode};
+use types::*;
+
+const AL: Word = 0xE000_0000;
+const IMMEDIATE: Word = 0x0200_0000;
+const LOAD: Word = 0x0010_0000;
+const BYTE: Word = 0x0040_0000;
+
+const AND: Word = 0b0000;
+const EOR: Word = 0b0001;
+const SUB: Word = 0b0010;
+const RSB: Word = 0b0011;
+const ADD: Word = 0b0100;
+const ADC: Word = 0b0101;
+const SBC: Word = 0b0110;
+const TST: Word = 0b1000;
+const CMP: Word = 0b1010;
+const CMN: Word = 0b1011;
+const ORR: Word = 0b1100;
+const MOV: Word = 0b1101;
+const BIC: Word = 0b1110;
+const MVN: Word = 0b1111;
+
+fn data_processing(op: Word, s: bool, rn: usize, rd: usize, operand2: Word) -> Word {
+    AL | op << 21 | (s as Word) << 20 | (rn as Word) << 16 | (rd as Word) << 12 | operand2
+}
+
+fn shift_type(op: Opcode) -> Word {
+    match op {
+        Opcode::LSL => 0b00,
+        Opcode::LSR => 0b01,
+        Opcode::ASR => 0b10,
+        _ => 0b11,
+    }
+}
+
+fn load_bit(dec: &Decoder) -> Word {
+    if dec.is_load() {
+        LOAD
+    } else {
+        0
+    }
+}
+
+fn move_shifted_register(dec: &Decoder) -> Word {
+    let operand2 = dec.get_offset5() << 7 | shift_type(dec.opcode()) << 5 | dec.get_Rs() as Word;
+    data_processing(MOV, true, 0, dec.get_Rd(), operand2)
+}
+
+fn add_subtract(dec: &Decoder) -> Word {
+    let op = if dec.opcode() == Opcode::ADD {
+        ADD
+    } else {
+        SUB
+    };
+    let operand2 = if dec.has_I() {
+        IMMEDIATE | dec.get_offset3()
+    } else {
+        dec.get_Rn() as Word
+    };
+    data_processing(op, true, dec.get_Rs(), dec.get_Rd(), operand2)
+}
+
+fn immediate(dec: &Decoder) -> Word {
+    let rd = dec.get_Rd();
+    let operand2 = IMMEDIATE | dec.get_imm8();
+    match dec.opcode() {
+        Opcode::MOV => data_processing(MOV, true, 0, rd, operand2),
+        Opcode::CMP => data_processing(CMP, true, rd, 0, operand2),
+        Opcode::ADD => data_processing(ADD, true, rd, rd, operand2),
+        _ => data_processing(SUB, true, rd, rd, operand2),
+    }
+}
+
+fn alu(dec: &Decoder) -> Word {
+    let rd = dec.get_Rd();
+    let rs = dec.get_Rs();
+    let operand2 = rs as Word;
+    match dec.opcode() {
+        Opcode::AND => data_processing(AND, true, rd, rd, operand2),
+        Opcode::EOR => data_processing(EOR, true, rd, rd, operand2),
+        Opcode::ADC => data_processing(ADC, true, rd, rd, operand2),
+        Opcode::SBC => data_processing(SBC, true, rd, rd, operand2),
+        Opcode::ORR => data_processing(ORR, true, rd, rd, operand2),
+        Opcode::BIC => data_processing(BIC, true, rd, rd, operand2),
+        Opcode::TST => data_processing(TST, true, rd, 0, operand2),
+        Opcode::CMP => data_processing(CMP, true, rd, 0, operand2),
+        Opcode::CMN => data_processing(CMN, true, rd, 0, operand2),
+        Opcode::MVN => data_processing(MVN, true, 0, rd, operand2),
+        // rsbs Rd, Rs, #0
+        Opcode::NEG => data_processing(RSB, true, rs, rd, IMMEDIATE),
+        // muls Rd, Rs, Rd
+        Opcode::MUL => AL | 0x0010_0090 | (rd as Word) << 16 | (rd as Word) << 8 | rs as Word,
+        // movs Rd, Rd, <shift> Rs
+        op => {
+            let operand2 = (rs as Word) << 8 | shift_type(op) << 5 | 0x10 | rd as Word;
+            data_processing(MOV, true, 0, rd, operand2)
+        }
+    }
+}
+
+// Only CMP sets the condition codes.
+fn hi_register(dec: &Decoder) -> Word {
+    let rd = dec.get_Rd();
+    let rs = dec.get_Rs();
+    match dec.opcode() {
+        Opcode::ADD => data_processing(ADD, false, rd, rd, rs as Word),
+        Opcode::CMP => data_processing(CMP, true, rd, 0, rs as Word),
+        Opcode::MOV => data_processing(MOV, false, 0, rd, rs as Word),
+        _ => AL | 0x012F_FF10 | rs as Word,
+    }
+}
+
+// ldr/str Rd, [Rb, Ro]
+fn load_store_register_offset(dec: &Decoder) -> Word {
+    let op = match dec.opcode() {
+        Opcode::STR => 0,
+        Opcode::STRB => BYTE,
+        Opcode::LDR => LOAD,
+        _ => LOAD | BYTE,
+    };
+    AL | 0x0780_0000
+        | op
+        | (dec.get_Rb() as Word) << 16
+        | (dec.get_Rd() as Word) << 12
+        | dec.get_Ro() as Word
+}
+
+// strh/ldrh/ldsb/ldsh Rd, [Rb, Ro]
+fn load_store_sign_extended(dec: &Decoder) -> Word {
+    let op = match dec.opcode() {
+        Opcode::STRH => 0x20,
+        Opcode::LDRH => LOAD | 0x20,
+        Opcode::LDSB => LOAD | 0x40,
+        _ => LOAD | 0x60,
+    };
+    AL | 0x0180_0090
+        | op
+        | (dec.get_Rb() as Word) << 16
+        | (dec.get_Rd() as Word) << 12
+        | dec.get_Ro() as Word
+}
+
+// ldr/str Rd, [Rb, #imm], the offset is in words unless the transfer is a byte.
+fn load_store_immediate_offset(dec: &Decoder) -> Word {
+    let (byte, offset) = match dec.opcode() {
+        Opcode::STRB | Opcode::LDRB => (BYTE, dec.get_offset5()),
+        _ => (0, dec.get_offset5() << 2),
+    };
+    AL | 0x0580_0000
+        | byte
+        | load_bit(dec)
+        | (dec.get_Rb() as Word) << 16
+        | (dec.get_Rd() as Word) << 12
+        | offset
+}
+
+// ldrh/strh Rd, [Rb, #imm]
+fn load_store_halfword(dec: &Decoder) -> Word {
+    let offset = dec.get_offset5() << 1;
+    AL | 0x01C0_00B0
+        | load_bit(dec)
+        | (dec.get_Rb() as Word) << 16
+        | (dec.get_Rd() as Word) << 12
+        | (offset & 0xF0) << 4
+        | offset & 0xF
+}
+
+// ldr/str Rd, [SP, #imm]
+fn sp_relative_load_store(dec: &Decoder) -> Word {
+    AL | 0x058D_0000 | load_bit(dec) | (dec.get_Rd() as Word) << 12 | dec.get_imm8() << 2
+}
+
+// add Rd, SP, #imm, imm8 << 2 is encoded as imm8 rotated right by 30.
+fn load_address(dec: &Decoder) -> Word {
+    data_processing(
+        ADD,
+        false,
+        SP,
+        dec.get_Rd(),
+        IMMEDIATE | 0xF00 | dec.get_imm8(),
+    )
+}
+
+// add/sub SP, #imm
+fn add_offset_to_sp(dec: &Decoder) -> Word {
+    let offset = dec.get_sp_offset();
+    let op = if offset < 0 { SUB } else { ADD };
+    let imm = offset.abs() as Word >> 2;
+    data_processing(op, false, SP, SP, IMMEDIATE | 0xF00 | imm)
+}
+
+// push is stmdb sp!, {Rlist, LR} and pop is ldmia sp!, {Rlist, PC}.
+fn push_pop(dec: &Decoder) -> Word {
+    let rlist = dec.get_register_list();
+    let r = dec.has_R() as Word;
+    if dec.opcode() == Opcode::PUSH {
+        AL | 0x092D_0000 | r << 14 | rlist
+    } else {
+        AL | 0x08BD_0000 | r << 15 | rlist
+    }
+}
+
+// ldmia/stmia Rb!, {Rlist}
+fn multi_load_and_store(dec: &Decoder) -> Word {
+    AL | 0x08A0_0000 | load_bit(dec) | (dec.get_Rb() as Word) << 16 | dec.get_register_list()
+}
+
+// Returns the ARM instruction which has the same effect as `dec`, flags included.
+// PC relative addressing, branches, SWI and undefined instructions have none.
+pub fn to_arm(dec: &Decoder) -> Word {
+    match dec.format() {
+        Format::MoveShiftedRegister => move_shifted_register(dec),
+        Format::AddSubtract => add_subtract(dec),
+        Format::Immediate => immediate(dec),
+        Format::ALU => alu(dec),
+        Format::HiRegister => hi_register(dec),
+        Format::LoadStoreRegisterOffset => load_store_register_offset(dec),
+        Format::LoadStoreSignExtended => load_store_sign_extended(dec),
+        Format::LoadStoreImmediateOffset => load_store_immediate_offset(dec),
+        Format::LoadStoreHalfWord => load_store_halfword(dec),
+        Format::SPRelativeLoadStore => sp_relative_load_store(dec),
+        Format::LoadAddress => load_address(dec),
+        Format::AddOffsetToSP => add_offset_to_sp(dec),
+        Format::PushPop => push_pop(dec),
+        Format::MultiLoadAndStore => multi_load_and_store(dec),
+        f => unreachable!("{:?} has no ARM equivalent", f),
+    }
+}
+
+#[test]
+fn translate_push_lr() {
+    use decoder::thumb::decode;
+    // push {r4, lr}
+    assert_eq!(to_arm(&*decode(0xB510)), 0xE92D_4010);
+}
+
+#[test]
+fn translate_ldrh_imm() {
+    use decoder::thumb::decode;
+    // ldrh r0, [r1, #0x3E]
+    assert_eq!(to_arm(&*decode(0x8FC8)), 0xE1D1_03BE);
+}
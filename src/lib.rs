@@ -0,0 +1,24 @@
+#![cfg_attr(feature = "clippy", feature(plugin))]
+#![cfg_attr(feature = "clippy", plugin(clippy))]
+
+extern crate byteorder;
+#[macro_use]
+extern crate log;
+
+pub mod bus;
+mod constants;
+pub mod coprocessor;
+pub mod core;
+mod decoder;
+pub mod device;
+pub mod error;
+pub mod exception;
+mod instructions;
+pub mod memory;
+pub mod mmu;
+pub mod registers;
+mod swi;
+pub mod types;
+
+// Peripherals are written against these outside of this crate.
+pub use device::{Device, Interrupt, Width};
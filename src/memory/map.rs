@@ -0,0 +1,481 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use bus::{Bus, BusError};
+use device::{Device, Interrupt, Width};
+use types::*;
+
+use super::ram::Ram;
+use super::readable::*;
+use super::rom::Rom;
+use super::writable::*;
+
+// What happens on an access to an address no region is mapped to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnmappedAccess {
+    // Panics with the address, for catching wild accesses early.
+    Panic,
+    // Reads return zero and writes are dropped, both are logged.
+    Ignore,
+    // Fails with `BusError::Unmapped`, which the core turns into an abort.
+    Abort,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MapError {
+    ZeroSize,
+    // Base and size of the region which is already mapped.
+    Overlap(Word, Word),
+    // No region is mapped at the base.
+    NotMapped(Word),
+}
+
+// Cycles of one access to a region by width, indexed byte, halfword, word.
+// Whether an access is sequential (S) or non-sequential (N) is stated by the core
+// with `Bus::set_sequential`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct WaitStates {
+    pub n: [u32; 3],
+    pub s: [u32; 3],
+}
+
+impl WaitStates {
+    // The same costs for every width.
+    pub fn new(n: u32, s: u32) -> Self {
+        WaitStates {
+            n: [n; 3],
+            s: [s; 3],
+        }
+    }
+
+    fn cycles(&self, width: Width, sequential: bool) -> u32 {
+        let i = match width {
+            Width::Byte => 0,
+            Width::HalfWord => 1,
+            Width::Word => 2,
+        };
+        if sequential {
+            self.s[i]
+        } else {
+            self.n[i]
+        }
+    }
+}
+
+// Zero wait states, every access takes one cycle.
+impl Default for WaitStates {
+    fn default() -> Self {
+        WaitStates::new(1, 1)
+    }
+}
+
+enum Backing {
+    Rom(Rc<RefCell<Rom>>),
+    Ram(Rc<RefCell<Ram>>),
+    // Devices see the offset from the base of their region.
+    Device(Rc<RefCell<Device>>),
+}
+
+struct Region {
+    base: Word,
+    size: Word,
+    backing: Backing,
+    wait_states: WaitStates,
+}
+
+impl Region {
+    fn contains(&self, addr: Word, width: Word) -> bool {
+        addr >= self.base && (addr - self.base) as u64 + width as u64 <= self.size as u64
+    }
+
+    fn overlaps(&self, base: Word, size: Word) -> bool {
+        (base as u64) < self.base as u64 + self.size as u64
+            && (self.base as u64) < base as u64 + size as u64
+    }
+}
+
+// Routes accesses to the regions registered by address range.
+// An access must lie entirely in one region, otherwise it is unmapped.
+pub struct MemoryMap {
+    regions: Vec<Region>,
+    unmapped: UnmappedAccess,
+    // Set by the core for the following accesses.
+    sequential: bool,
+    // Cycles of the accesses since `take_access_cycles`.
+    access_cycles: Cell<u32>,
+}
+
+impl MemoryMap {
+    pub fn new(unmapped: UnmappedAccess) -> Self {
+        MemoryMap {
+            regions: Vec::new(),
+            unmapped,
+            sequential: false,
+            access_cycles: Cell::new(0),
+        }
+    }
+
+    pub fn map_rom(&mut self, base: Word, rom: Rc<RefCell<Rom>>) -> Result<(), MapError> {
+        let size = rom.borrow().len() as Word;
+        self.map(base, size, Backing::Rom(rom))
+    }
+
+    pub fn map_ram(&mut self, base: Word, ram: Rc<RefCell<Ram>>) -> Result<(), MapError> {
+        let size = ram.borrow().len() as Word;
+        self.map(base, size, Backing::Ram(ram))
+    }
+
+    pub fn map_device(
+        &mut self,
+        base: Word,
+        size: Word,
+        device: Rc<RefCell<Device>>,
+    ) -> Result<(), MapError> {
+        self.map(base, size, Backing::Device(device))
+    }
+
+    pub fn set_wait_states(&mut self, base: Word, wait_states: WaitStates) -> Result<(), MapError> {
+        match self.regions.iter_mut().find(|r| r.base == base) {
+            Some(region) => {
+                region.wait_states = wait_states;
+                Ok(())
+            }
+            None => Err(MapError::NotMapped(base)),
+        }
+    }
+
+    fn map(&mut self, base: Word, size: Word, backing: Backing) -> Result<(), MapError> {
+        if size == 0 {
+            return Err(MapError::ZeroSize);
+        }
+        if let Some(region) = self.regions.iter().find(|r| r.overlaps(base, size)) {
+            return Err(MapError::Overlap(region.base, region.size));
+        }
+        debug!("map {:x} - {:x}", base, base as u64 + size as u64 - 1);
+        self.regions.push(Region {
+            base,
+            size,
+            backing,
+            wait_states: WaitStates::default(),
+        });
+        Ok(())
+    }
+
+    fn find(&self, addr: Word, width: Word) -> Option<(&Region, Word)> {
+        self.regions
+            .iter()
+            .find(|r| r.contains(addr, width))
+            .map(|r| (r, addr - r.base))
+    }
+
+    // Finds the region and charges the cycles of the access to it.
+    // INFO: An unmapped access takes one cycle.
+    fn access(&self, addr: Word, width: Width) -> Option<(&Region, Word)> {
+        let found = self.find(addr, width.bytes());
+        let cycles = match found {
+            Some((region, _)) => {
+                debug!(
+                    "{} access to {:x}",
+                    if self.sequential { "S" } else { "N" },
+                    addr
+                );
+                region.wait_states.cycles(width, self.sequential)
+            }
+            None => 1,
+        };
+        self.access_cycles.set(self.access_cycles.get() + cycles);
+        found
+    }
+
+    fn devices<'a>(&'a self) -> Box<Iterator<Item = &'a Rc<RefCell<Device>>> + 'a> {
+        Box::new(self.regions.iter().filter_map(|r| match r.backing {
+            Backing::Device(ref device) => Some(device),
+            _ => None,
+        }))
+    }
+
+    fn is_asserted(&self, interrupt: Interrupt) -> bool {
+        self.devices()
+            .any(|device| device.borrow().interrupt() == Some(interrupt))
+    }
+
+    fn unmapped_read(&self, addr: Word) -> Result<Word, BusError> {
+        match self.unmapped {
+            UnmappedAccess::Panic => panic!("read from unmapped address {:x}", addr),
+            UnmappedAccess::Ignore => {
+                warn!("read from unmapped address {:x}", addr);
+                Ok(0)
+            }
+            UnmappedAccess::Abort => Err(BusError::Unmapped),
+        }
+    }
+
+    fn unmapped_write(&self, addr: Word) -> Result<(), BusError> {
+        match self.unmapped {
+            UnmappedAccess::Panic => panic!("write to unmapped address {:x}", addr),
+            UnmappedAccess::Ignore => {
+                warn!("write to unmapped address {:x}", addr);
+                Ok(())
+            }
+            UnmappedAccess::Abort => Err(BusError::Unmapped),
+        }
+    }
+}
+
+impl Bus for MemoryMap {
+    fn read_byte(&self, addr: Word) -> Result<Byte, BusError> {
+        match self.access(addr, Width::Byte) {
+            Some((region, offset)) => match region.backing {
+                Backing::Rom(ref rom) => Ok(rom.borrow().read_byte(offset)),
+                Backing::Ram(ref ram) => Ok(ram.borrow().read_byte(offset)),
+                Backing::Device(ref device) => device
+                    .borrow_mut()
+                    .read(offset, Width::Byte)
+                    .map(|data| data as Byte),
+            },
+            None => self.unmapped_read(addr).map(|data| data as Byte),
+        }
+    }
+
+    fn read_halfword(&self, addr: Word) -> Result<HalfWord, BusError> {
+        match self.access(addr, Width::HalfWord) {
+            Some((region, offset)) => match region.backing {
+                Backing::Rom(ref rom) => Ok(rom.borrow().read_halfword(offset)),
+                Backing::Ram(ref ram) => Ok(ram.borrow().read_halfword(offset)),
+                Backing::Device(ref device) => device
+                    .borrow_mut()
+                    .read(offset, Width::HalfWord)
+                    .map(|data| data as HalfWord),
+            },
+            None => self.unmapped_read(addr).map(|data| data as HalfWord),
+        }
+    }
+
+    fn read_word(&self, addr: Word) -> Result<Word, BusError> {
+        match self.access(addr, Width::Word) {
+            Some((region, offset)) => match region.backing {
+                Backing::Rom(ref rom) => Ok(rom.borrow().read_word(offset)),
+                Backing::Ram(ref ram) => Ok(ram.borrow().read_word(offset)),
+                Backing::Device(ref device) => device.borrow_mut().read(offset, Width::Word),
+            },
+            None => self.unmapped_read(addr),
+        }
+    }
+
+    // INFO: Writes to ROM fail with a permission error.
+    fn write_byte(&mut self, addr: Word, data: Byte) -> Result<(), BusError> {
+        match self.access(addr, Width::Byte) {
+            Some((region, offset)) => match region.backing {
+                Backing::Rom(_) => Err(BusError::Permission),
+                Backing::Ram(ref ram) => {
+                    ram.borrow_mut().write_byte(offset, data);
+                    Ok(())
+                }
+                Backing::Device(ref device) => {
+                    device.borrow_mut().write(offset, Width::Byte, data as Word)
+                }
+            },
+            None => self.unmapped_write(addr),
+        }
+    }
+
+    fn write_halfword(&mut self, addr: Word, data: HalfWord) -> Result<(), BusError> {
+        match self.access(addr, Width::HalfWord) {
+            Some((region, offset)) => match region.backing {
+                Backing::Rom(_) => Err(BusError::Permission),
+                Backing::Ram(ref ram) => {
+                    ram.borrow_mut().write_halfword(offset, data);
+                    Ok(())
+                }
+                Backing::Device(ref device) => {
+                    device
+                        .borrow_mut()
+                        .write(offset, Width::HalfWord, data as Word)
+                }
+            },
+            None => self.unmapped_write(addr),
+        }
+    }
+
+    fn write_word(&mut self, addr: Word, data: Word) -> Result<(), BusError> {
+        match self.access(addr, Width::Word) {
+            Some((region, offset)) => match region.backing {
+                Backing::Rom(_) => Err(BusError::Permission),
+                Backing::Ram(ref ram) => {
+                    ram.borrow_mut().write_word(offset, data);
+                    Ok(())
+                }
+                Backing::Device(ref device) => device.borrow_mut().write(offset, Width::Word, data),
+            },
+            None => self.unmapped_write(addr),
+        }
+    }
+
+    fn set_sequential(&mut self, sequential: bool) {
+        self.sequential = sequential;
+    }
+
+    fn take_access_cycles(&mut self) -> u32 {
+        self.access_cycles.replace(0)
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        for device in self.devices() {
+            device.borrow_mut().tick(cycles);
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.is_asserted(Interrupt::IRQ)
+    }
+
+    fn fiq(&self) -> bool {
+        self.is_asserted(Interrupt::FIQ)
+    }
+}
+
+#[test]
+fn route_to_ram_by_address() {
+    let ram = Rc::new(RefCell::new(Ram::new(vec![0; 0x100])));
+    let mut map = MemoryMap::new(UnmappedAccess::Panic);
+    map.map_ram(0x4000_0000, ram.clone()).unwrap();
+    map.write_word(0x4000_0010, 0x1234_5678).unwrap();
+    map.write_halfword(0x4000_0020, 0xABCD).unwrap();
+    assert_eq!(ram.borrow().read_word(0x10), 0x1234_5678);
+    assert_eq!(map.read_halfword(0x4000_0020), Ok(0xABCD));
+    assert_eq!(map.read_byte(0x4000_0011), Ok(0x56));
+}
+
+#[test]
+fn write_to_rom_is_rejected() {
+    let rom = Rc::new(RefCell::new(Rom::new(4, vec![0x01, 0x02, 0x03, 0x04])));
+    let mut map = MemoryMap::new(UnmappedAccess::Panic);
+    map.map_rom(0, rom).unwrap();
+    assert_eq!(map.write_word(0, 0), Err(BusError::Permission));
+    assert_eq!(map.read_word(0), Ok(0x0403_0201));
+}
+
+#[test]
+fn sequential_access_takes_s_cycles() {
+    let ram = Rc::new(RefCell::new(Ram::new(vec![0; 0x100])));
+    let mut map = MemoryMap::new(UnmappedAccess::Panic);
+    map.map_ram(0x1000, ram).unwrap();
+    let wait_states = WaitStates {
+        n: [3, 3, 4],
+        s: [1, 1, 2],
+    };
+    map.set_wait_states(0x1000, wait_states).unwrap();
+    map.read_word(0x1000).unwrap();
+    map.set_sequential(true);
+    map.write_word(0x1004, 0).unwrap();
+    map.read_byte(0x1008).unwrap();
+    // Only the core knows, the following address does not make an access sequential.
+    map.set_sequential(false);
+    map.read_halfword(0x100A).unwrap();
+    assert_eq!(map.take_access_cycles(), 4 + 2 + 1 + 3);
+    assert_eq!(map.take_access_cycles(), 0);
+    assert_eq!(
+        map.set_wait_states(0x2000, WaitStates::default()),
+        Err(MapError::NotMapped(0x2000))
+    );
+}
+
+#[test]
+fn overlapping_region_is_rejected() {
+    let mut map = MemoryMap::new(UnmappedAccess::Panic);
+    let ram = Rc::new(RefCell::new(Ram::new(vec![0; 0x100])));
+    map.map_ram(0x1000, ram.clone()).unwrap();
+    assert_eq!(
+        map.map_ram(0x10FC, ram.clone()),
+        Err(MapError::Overlap(0x1000, 0x100))
+    );
+    assert_eq!(map.map_ram(0x1100, ram), Ok(()));
+}
+
+#[test]
+fn unmapped_access_is_ignored() {
+    let mut map = MemoryMap::new(UnmappedAccess::Ignore);
+    let ram = Rc::new(RefCell::new(Ram::new(vec![0xFF; 0x100])));
+    map.map_ram(0, ram).unwrap();
+    map.write_word(0x100, 0x1234_5678).unwrap();
+    assert_eq!(map.read_word(0x100), Ok(0));
+    // Straddles the end of the region.
+    assert_eq!(map.read_word(0xFE), Ok(0));
+}
+
+#[test]
+fn unmapped_access_is_aborted() {
+    let mut map = MemoryMap::new(UnmappedAccess::Abort);
+    assert_eq!(map.read_byte(0x100), Err(BusError::Unmapped));
+    assert_eq!(map.write_halfword(0x100, 0), Err(BusError::Unmapped));
+}
+
+#[test]
+#[should_panic]
+fn unmapped_access_panics() {
+    let map = MemoryMap::new(UnmappedAccess::Panic);
+    let _ = map.read_word(0x8000_0000);
+}
+
+#[cfg(test)]
+struct MockDevice {
+    last_write: (Word, Width, Word),
+    cycles: u32,
+}
+
+#[cfg(test)]
+impl Device for MockDevice {
+    fn read(&mut self, offset: Word, width: Width) -> Result<Word, BusError> {
+        Ok(offset | width.bytes() << 8)
+    }
+
+    fn write(&mut self, offset: Word, width: Width, data: Word) -> Result<(), BusError> {
+        if offset >= 0x80 {
+            return Err(BusError::Device);
+        }
+        self.last_write = (offset, width, data);
+        Ok(())
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.cycles += cycles;
+    }
+
+    fn interrupt(&self) -> Option<Interrupt> {
+        if self.cycles >= 4 {
+            Some(Interrupt::FIQ)
+        } else {
+            None
+        }
+    }
+}
+
+#[test]
+fn device_sees_offset_and_width() {
+    let device = Rc::new(RefCell::new(MockDevice {
+        last_write: (0, Width::Byte, 0),
+        cycles: 0,
+    }));
+    let mut map = MemoryMap::new(UnmappedAccess::Panic);
+    map.map_device(0x1000_0000, 0x100, device.clone()).unwrap();
+    assert_eq!(map.read_word(0x1000_0010), Ok(0x0410));
+    assert_eq!(map.read_halfword(0x1000_0012), Ok(0x0212));
+    map.write_byte(0x1000_0021, 0xAB).unwrap();
+    assert_eq!(device.borrow().last_write, (0x21, Width::Byte, 0xAB));
+    assert_eq!(map.write_word(0x1000_0080, 0), Err(BusError::Device));
+}
+
+#[test]
+fn device_is_clocked_and_interrupts() {
+    let device = Rc::new(RefCell::new(MockDevice {
+        last_write: (0, Width::Byte, 0),
+        cycles: 0,
+    }));
+    let mut map = MemoryMap::new(UnmappedAccess::Panic);
+    map.map_device(0x1000_0000, 0x100, device).unwrap();
+    map.tick(3);
+    assert!(!map.fiq());
+    map.tick(1);
+    assert!(map.fiq());
+    assert!(!map.irq());
+}
@@ -1,9 +1,12 @@
 pub mod rom;
 pub mod ram;
+pub mod map;
 
 pub mod readable;
 pub mod writable;
 
+pub use self::map::{MapError, MemoryMap, UnmappedAccess, WaitStates};
+
 pub trait Raw {
     fn raw(&self, offset: u32) -> &[u8];
 }
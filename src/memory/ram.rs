@@ -10,6 +10,10 @@ impl Ram {
     pub fn new(buf: Vec<u8>) -> Self {
         Ram(buf.clone())
     }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
 }
 
 impl Raw for Ram {
@@ -11,6 +11,10 @@ impl Rom {
         init.extend(&buf[len..]);
         Rom(init)
     }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
 }
 
 impl Raw for Rom {
@@ -0,0 +1,381 @@
+pub mod tlb;
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use bus::{Bus, BusError};
+use coprocessor::cp15::*;
+use types::*;
+
+use self::tlb::{Tlb, TlbEntry};
+
+// Access control of a domain in DACR.
+const DOMAIN_CLIENT: Word = 0b01;
+const DOMAIN_MANAGER: Word = 0b11;
+
+fn fault(status: Word, domain: Word) -> BusError {
+    BusError::Mmu(domain << 4 | status)
+}
+
+// Translates the virtual addresses of the core to physical addresses on `bus`
+// and checks domains and access permissions, configured from CP15.
+// Addresses pass through unchanged while the MMU is disabled.
+pub struct Mmu<T> {
+    bus: Rc<RefCell<T>>,
+    enabled: bool,
+    // S and R bits of the control register, they change the meaning of AP = 0.
+    system: bool,
+    rom: bool,
+    ttb: Word,
+    dacr: Word,
+    privileged: bool,
+    // Filled by reads too, which only borrow the bus.
+    tlb: RefCell<Tlb>,
+    // Set by every access, the core uses it to tell fetches after a data access.
+    accessed: Cell<bool>,
+}
+
+impl<T> Mmu<T>
+where
+    T: Bus,
+{
+    pub fn new(bus: Rc<RefCell<T>>) -> Self {
+        Mmu {
+            bus,
+            enabled: false,
+            system: false,
+            rom: false,
+            ttb: 0,
+            dacr: 0,
+            privileged: true,
+            tlb: RefCell::new(Tlb::new()),
+            accessed: Cell::new(false),
+        }
+    }
+
+    // The physical bus, for tests to look at the memory behind the MMU.
+    #[cfg(test)]
+    pub fn bus(&self) -> &Rc<RefCell<T>> {
+        &self.bus
+    }
+
+    // Takes the control register, TTBR and DACR of CP15.
+    pub fn configure(&mut self, control: Word, ttb: Word, dacr: Word) {
+        self.enabled = control & CONTROL_M != 0;
+        self.system = control & CONTROL_S != 0;
+        self.rom = control & CONTROL_R != 0;
+        self.ttb = ttb & 0xFFFF_C000;
+        self.dacr = dacr;
+    }
+
+    // Accesses in User mode are checked against the user permissions.
+    pub fn set_privileged(&mut self, privileged: bool) {
+        self.privileged = privileged;
+    }
+
+    pub fn apply(&mut self, operation: TlbOperation) {
+        debug!("{:?}", operation);
+        match operation {
+            TlbOperation::InvalidateAll => self.tlb.borrow_mut().invalidate_all(),
+            TlbOperation::InvalidateEntry(va) => self.tlb.borrow_mut().invalidate(va),
+        }
+    }
+
+    // Returns whether there was an access since the last call.
+    pub fn take_accessed(&self) -> bool {
+        self.accessed.replace(false)
+    }
+
+    fn translate(&self, va: Word, write: bool) -> Result<Word, BusError> {
+        self.accessed.set(true);
+        if !self.enabled {
+            return Ok(va);
+        }
+        let cached = self.tlb.borrow().lookup(va);
+        let entry = match cached {
+            Some(entry) => entry,
+            None => {
+                let entry = self.walk(va)?;
+                self.tlb.borrow_mut().insert(entry);
+                entry
+            }
+        };
+        self.check_access(&entry, write)?;
+        Ok(entry.translate(va))
+    }
+
+    fn read_descriptor(&self, addr: Word, status: Word, domain: Word) -> Result<Word, BusError> {
+        self.bus.borrow().read_word(addr).map_err(|e| {
+            debug!("{:?} on page table walk at {:x}", e, addr);
+            fault(status, domain)
+        })
+    }
+
+    // INFO: The walk breaks the sequence, the access after it is non-sequential too.
+    fn walk(&self, va: Word) -> Result<TlbEntry, BusError> {
+        self.bus.borrow_mut().set_sequential(false);
+        let first =
+            self.read_descriptor(self.ttb | (va >> 20) << 2, FSR_EXTERNAL_ABORT_LEVEL1, 0)?;
+        debug!("first level descriptor of {:x} = {:x}", va, first);
+        let domain = (first >> 5) & 0xF;
+        let second_addr = match first & 0b11 {
+            // Section, 1MB.
+            0b10 => {
+                return Ok(TlbEntry {
+                    va: va & 0xFFF0_0000,
+                    pa: first & 0xFFF0_0000,
+                    mask: 0x000F_FFFF,
+                    domain,
+                    ap: (first >> 10) & 0b11,
+                    section: true,
+                })
+            }
+            // Coarse page table, 256 entries.
+            0b01 => first & 0xFFFF_FC00 | ((va >> 12) & 0xFF) << 2,
+            // Fine page table, 1024 entries.
+            0b11 => first & 0xFFFF_F000 | ((va >> 10) & 0x3FF) << 2,
+            _ => return Err(fault(FSR_TRANSLATION_SECTION, 0)),
+        };
+        let second = self.read_descriptor(second_addr, FSR_EXTERNAL_ABORT_LEVEL2, domain)?;
+        debug!("second level descriptor of {:x} = {:x}", va, second);
+        // Large and small pages have four subpages with an AP each, selected by the VA.
+        let (pa, mask, ap) = match second & 0b11 {
+            // Large page, 64KB with 16KB subpages.
+            0b01 => {
+                let subpage = (va >> 14) & 0b11;
+                (
+                    second & 0xFFFF_0000 | va & 0xC000,
+                    0x3FFF,
+                    second >> (4 + subpage * 2),
+                )
+            }
+            // Small page, 4KB with 1KB subpages.
+            0b10 => {
+                let subpage = (va >> 10) & 0b11;
+                (
+                    second & 0xFFFF_F000 | va & 0xC00,
+                    0x3FF,
+                    second >> (4 + subpage * 2),
+                )
+            }
+            // Tiny page, 1KB, only in fine page tables.
+            0b11 if first & 0b11 == 0b11 => (second & 0xFFFF_FC00, 0x3FF, second >> 4),
+            _ => return Err(fault(FSR_TRANSLATION_PAGE, domain)),
+        };
+        Ok(TlbEntry {
+            va: va & !mask,
+            pa,
+            mask,
+            domain,
+            ap: ap & 0b11,
+            section: false,
+        })
+    }
+
+    fn check_access(&self, entry: &TlbEntry, write: bool) -> Result<(), BusError> {
+        let (domain_fault, permission_fault) = if entry.section {
+            (FSR_DOMAIN_SECTION, FSR_PERMISSION_SECTION)
+        } else {
+            (FSR_DOMAIN_PAGE, FSR_PERMISSION_PAGE)
+        };
+        match (self.dacr >> (entry.domain * 2)) & 0b11 {
+            DOMAIN_MANAGER => Ok(()),
+            DOMAIN_CLIENT if self.is_permitted(entry.ap, write) => Ok(()),
+            DOMAIN_CLIENT => Err(fault(permission_fault, entry.domain)),
+            // No access, and the reserved encoding.
+            _ => Err(fault(domain_fault, entry.domain)),
+        }
+    }
+
+    fn is_permitted(&self, ap: Word, write: bool) -> bool {
+        match ap {
+            // Read only depending on S and R, S and R both set is unpredictable.
+            0b00 => {
+                !write
+                    && match (self.system, self.rom) {
+                        (true, false) => self.privileged,
+                        (false, true) => true,
+                        _ => false,
+                    }
+            }
+            0b01 => self.privileged,
+            0b10 => self.privileged || !write,
+            _ => true,
+        }
+    }
+}
+
+impl<T> Bus for Mmu<T>
+where
+    T: Bus,
+{
+    fn read_byte(&self, addr: Word) -> Result<Byte, BusError> {
+        let addr = self.translate(addr, false)?;
+        self.bus.borrow().read_byte(addr)
+    }
+
+    fn read_halfword(&self, addr: Word) -> Result<HalfWord, BusError> {
+        let addr = self.translate(addr, false)?;
+        self.bus.borrow().read_halfword(addr)
+    }
+
+    fn read_word(&self, addr: Word) -> Result<Word, BusError> {
+        let addr = self.translate(addr, false)?;
+        self.bus.borrow().read_word(addr)
+    }
+
+    fn write_byte(&mut self, addr: Word, data: Byte) -> Result<(), BusError> {
+        let addr = self.translate(addr, true)?;
+        self.bus.borrow_mut().write_byte(addr, data)
+    }
+
+    fn write_halfword(&mut self, addr: Word, data: HalfWord) -> Result<(), BusError> {
+        let addr = self.translate(addr, true)?;
+        self.bus.borrow_mut().write_halfword(addr, data)
+    }
+
+    fn write_word(&mut self, addr: Word, data: Word) -> Result<(), BusError> {
+        let addr = self.translate(addr, true)?;
+        self.bus.borrow_mut().write_word(addr, data)
+    }
+
+    fn lock(&mut self) {
+        self.bus.borrow_mut().lock();
+    }
+
+    fn set_sequential(&mut self, sequential: bool) {
+        self.bus.borrow_mut().set_sequential(sequential);
+    }
+
+    fn unlock(&mut self) {
+        self.bus.borrow_mut().unlock();
+    }
+
+    fn take_access_cycles(&mut self) -> u32 {
+        self.bus.borrow_mut().take_access_cycles()
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.bus.borrow_mut().tick(cycles);
+    }
+
+    fn irq(&self) -> bool {
+        self.bus.borrow().irq()
+    }
+
+    fn fiq(&self) -> bool {
+        self.bus.borrow().fiq()
+    }
+}
+
+#[cfg(test)]
+fn new_mmu(descriptors: &[(Word, Word)], dacr: Word) -> Mmu<::memory::MemoryMap> {
+    use memory::ram::Ram;
+    use memory::{MemoryMap, UnmappedAccess};
+
+    let mut bus = MemoryMap::new(UnmappedAccess::Abort);
+    let ram = Ram::new(vec![0; 0x20_0000]);
+    bus.map_ram(0, Rc::new(RefCell::new(ram))).unwrap();
+    for &(addr, descriptor) in descriptors {
+        bus.write_word(addr, descriptor).unwrap();
+    }
+    let mut mmu = Mmu::new(Rc::new(RefCell::new(bus)));
+    mmu.configure(CONTROL_M, 0x4000, dacr);
+    mmu
+}
+
+#[test]
+fn disabled_mmu_passes_addresses_through() {
+    let mut mmu = new_mmu(&[], 0);
+    mmu.configure(0, 0x4000, 0);
+    mmu.write_word(0x100, 0x1234_5678).unwrap();
+    assert_eq!(mmu.read_word(0x100), Ok(0x1234_5678));
+}
+
+#[test]
+fn section_is_translated() {
+    // VA 0x8010_0000 -> PA 0x0000_0000, domain 1, AP = 3.
+    let mmu = new_mmu(&[(0x4000 | 0x801 << 2, 0x0000_0C22)], 0b01 << 2);
+    mmu.bus()
+        .borrow_mut()
+        .write_word(0x0010, 0xCAFE_BABE)
+        .unwrap();
+    assert_eq!(mmu.read_word(0x8010_0010), Ok(0xCAFE_BABE));
+    assert_eq!(
+        mmu.read_word(0x8020_0000),
+        Err(BusError::Mmu(FSR_TRANSLATION_SECTION))
+    );
+}
+
+#[test]
+fn pages_of_coarse_and_fine_tables_are_translated() {
+    let mmu = new_mmu(
+        &[
+            // VA 0x0000_0000, coarse table at 0x8000.
+            (0x4000, 0x0000_8001),
+            // Small page at VA 0x0000_1000 -> PA 0x0002_0000, AP = 3.
+            (0x8000 | 1 << 2, 0x0002_0FF2),
+            // Large page at VA 0x0001_0000 -> PA 0x0003_0000, AP = 3.
+            // INFO: Its entry is repeated 16 times, only the one used is written.
+            (0x8000 | 0x18 << 2, 0x0003_FFF1),
+            // VA 0x0010_0000, fine table at 0x9000.
+            (0x4004, 0x0000_9003),
+            // Tiny page at VA 0x0010_0400 -> PA 0x0004_0400, AP = 3.
+            (0x9000 | 1 << 2, 0x0004_0433),
+        ],
+        0b01,
+    );
+    {
+        let bus = mmu.bus();
+        let mut bus = bus.borrow_mut();
+        bus.write_word(0x0002_0204, 0x1111_1111).unwrap();
+        bus.write_word(0x0003_8008, 0x2222_2222).unwrap();
+        bus.write_word(0x0004_0408, 0x3333_3333).unwrap();
+    }
+    assert_eq!(mmu.read_word(0x0000_1204), Ok(0x1111_1111));
+    assert_eq!(mmu.read_word(0x0001_8008), Ok(0x2222_2222));
+    assert_eq!(mmu.read_word(0x0010_0408), Ok(0x3333_3333));
+    assert_eq!(
+        mmu.read_word(0x0000_2000),
+        Err(BusError::Mmu(FSR_TRANSLATION_PAGE))
+    );
+}
+
+#[test]
+fn permissions_depend_on_mode_and_domain() {
+    // VA 0x0000_0000 -> PA 0x0000_0000, domain 2, AP = 2.
+    let mut mmu = new_mmu(&[(0x4000, 0x0000_0842)], 0b01 << 4);
+    mmu.write_word(0x100, 1).unwrap();
+    mmu.set_privileged(false);
+    assert_eq!(mmu.read_word(0x100), Ok(1));
+    assert_eq!(
+        mmu.write_word(0x100, 2),
+        Err(BusError::Mmu(2 << 4 | FSR_PERMISSION_SECTION))
+    );
+    // Manager domains are not checked against AP.
+    mmu.configure(CONTROL_M, 0x4000, 0b11 << 4);
+    assert_eq!(mmu.write_word(0x100, 2), Ok(()));
+    mmu.configure(CONTROL_M, 0x4000, 0);
+    assert_eq!(
+        mmu.read_word(0x100),
+        Err(BusError::Mmu(2 << 4 | FSR_DOMAIN_SECTION))
+    );
+}
+
+#[test]
+fn tlb_keeps_translation_until_flushed() {
+    let mut mmu = new_mmu(&[(0x4000 | 0x800 << 2, 0x0000_0C02)], 0b01);
+    mmu.bus()
+        .borrow_mut()
+        .write_word(0x0010_0000, 0xAAAA_AAAA)
+        .unwrap();
+    assert_eq!(mmu.read_word(0x8000_0000), Ok(0));
+    // Remap the section to PA 0x0010_0000.
+    mmu.bus()
+        .borrow_mut()
+        .write_word(0x4000 | 0x800 << 2, 0x0010_0C02)
+        .unwrap();
+    assert_eq!(mmu.read_word(0x8000_0000), Ok(0));
+    mmu.apply(TlbOperation::InvalidateEntry(0x8000_0000));
+    assert_eq!(mmu.read_word(0x8000_0000), Ok(0xAAAA_AAAA));
+}
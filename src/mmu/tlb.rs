@@ -0,0 +1,104 @@
+use types::Word;
+
+// ARM920T has 64 entries in each of its TLBs.
+const TLB_ENTRIES: usize = 64;
+
+// Result of a page table walk. An entry covers a section, or a subpage for
+// large and small pages since each of their subpages has its own AP.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TlbEntry {
+    pub va: Word,
+    pub pa: Word,
+    // Size of the covered range - 1.
+    pub mask: Word,
+    pub domain: Word,
+    pub ap: Word,
+    // Faults of sections and pages have different status.
+    pub section: bool,
+}
+
+impl TlbEntry {
+    fn contains(&self, va: Word) -> bool {
+        va & !self.mask == self.va
+    }
+
+    pub fn translate(&self, va: Word) -> Word {
+        self.pa | va & self.mask
+    }
+}
+
+// Entries are replaced round robin once the TLB is full.
+pub struct Tlb {
+    entries: Vec<TlbEntry>,
+    next: usize,
+}
+
+impl Tlb {
+    pub fn new() -> Self {
+        Tlb {
+            entries: Vec::with_capacity(TLB_ENTRIES),
+            next: 0,
+        }
+    }
+
+    pub fn lookup(&self, va: Word) -> Option<TlbEntry> {
+        self.entries.iter().find(|e| e.contains(va)).cloned()
+    }
+
+    pub fn insert(&mut self, entry: TlbEntry) {
+        if self.entries.len() < TLB_ENTRIES {
+            self.entries.push(entry);
+        } else {
+            self.entries[self.next] = entry;
+            self.next = (self.next + 1) % TLB_ENTRIES;
+        }
+    }
+
+    pub fn invalidate_all(&mut self) {
+        self.entries.clear();
+        self.next = 0;
+    }
+
+    pub fn invalidate(&mut self, va: Word) {
+        self.entries.retain(|e| !e.contains(va));
+        self.next = 0;
+    }
+}
+
+#[test]
+fn lookup_by_covered_range() {
+    let mut tlb = Tlb::new();
+    tlb.insert(TlbEntry {
+        va: 0x1234_5000,
+        pa: 0x8000_0000,
+        mask: 0x3FF,
+        domain: 0,
+        ap: 3,
+        section: false,
+    });
+    assert_eq!(
+        tlb.lookup(0x1234_53FC).unwrap().translate(0x1234_53FC),
+        0x8000_03FC
+    );
+    assert_eq!(tlb.lookup(0x1234_5400), None);
+    tlb.invalidate(0x1234_5004);
+    assert_eq!(tlb.lookup(0x1234_5000), None);
+}
+
+#[test]
+fn oldest_entry_is_replaced_when_full() {
+    let mut tlb = Tlb::new();
+    for i in 0..(TLB_ENTRIES as Word + 1) {
+        tlb.insert(TlbEntry {
+            va: i << 20,
+            pa: i << 20,
+            mask: 0x000F_FFFF,
+            domain: 0,
+            ap: 3,
+            section: true,
+        });
+    }
+    assert_eq!(tlb.lookup(0), None);
+    assert!(tlb.lookup(1 << 20).is_some());
+    assert!(tlb.lookup((TLB_ENTRIES as Word) << 20).is_some());
+}
@@ -34,6 +34,32 @@ impl Mode {
             Mode::System => PSR::MODE_SYSTEM,
         }
     }
+
+    pub fn from_bits(bits: u32) -> Option<Mode> {
+        match bits & PSR::MODE_MASK {
+            PSR::MODE_USER => Some(Mode::User),
+            PSR::MODE_FIQ => Some(Mode::FIQ),
+            PSR::MODE_IRQ => Some(Mode::IRQ),
+            PSR::MODE_SUPERVISOR => Some(Mode::Supervisor),
+            PSR::MODE_ABORT => Some(Mode::Abort),
+            PSR::MODE_UNDEFINED => Some(Mode::Undefined),
+            PSR::MODE_SYSTEM => Some(Mode::System),
+            _ => None,
+        }
+    }
+
+    /// The index of the banked R13/R14, User and System share the same registers.
+    pub fn bank(self) -> usize {
+        match self {
+            Mode::System => Mode::User as usize,
+            m => m as usize,
+        }
+    }
+
+    /// User and System mode have no SPSR.
+    pub fn has_spsr(self) -> bool {
+        self != Mode::User && self != Mode::System
+    }
 }
 
 /// The Program Status Register.
@@ -47,16 +73,22 @@ impl PSR {
     const NON_RESERVED_MASK: u32 = 0b11110000_00000000_00000000_11111111_u32;
     //                               NZCV                       IFTMMMMM
 
-    const FLAGS_MASK: u32 = 0xF0000000_u32;
+    #[cfg(not(feature = "armv5te"))]
+    pub const FLAGS_MASK: u32 = 0xF0000000_u32;
+    // NZCVQ, Q is the sticky overflow flag of the ARMv5TE saturating instructions.
+    #[cfg(feature = "armv5te")]
+    pub const FLAGS_MASK: u32 = 0xF8000000_u32;
     const N_FLAG_BIT: u32 = 31;
     const Z_FLAG_BIT: u32 = 30;
     const C_FLAG_BIT: u32 = 29;
     const V_FLAG_BIT: u32 = 28;
+    #[cfg(feature = "armv5te")]
+    const Q_FLAG_BIT: u32 = 27;
 
     const IRQ_DISABLE_BIT: u32 = 7;
     const FIQ_DISABLE_BIT: u32 = 6;
 
-    const STATE_BIT: u32 = 5;
+    pub const STATE_BIT: u32 = 5;
 
     const MODE_MASK: u32 = 0b0001_1111;
     const MODE_USER: u32 = 0b1_0000;
@@ -76,15 +108,9 @@ impl PSR {
     }
 
     pub fn mode(&self) -> Mode {
-        match self.0 & PSR::MODE_MASK {
-            PSR::MODE_USER => Mode::User,
-            PSR::MODE_FIQ => Mode::FIQ,
-            PSR::MODE_IRQ => Mode::IRQ,
-            PSR::MODE_SUPERVISOR => Mode::Supervisor,
-            PSR::MODE_ABORT => Mode::Abort,
-            PSR::MODE_UNDEFINED => Mode::Undefined,
-            PSR::MODE_SYSTEM => Mode::System,
-            _ => {
+        match Mode::from_bits(self.0) {
+            Some(m) => m,
+            None => {
                 error!(
                     "PSR: Unrecognised mode bit pattern {:#010b}.",
                     self.0 & PSR::MODE_MASK
@@ -160,7 +186,7 @@ impl PSR {
 
     #[allow(non_snake_case)]
     pub fn set_C(&mut self, c: bool) {
-        self.0 = self.0 | ((c as u32) << PSR::C_FLAG_BIT);
+        self.0 = (self.0 & !(1 << PSR::C_FLAG_BIT)) | ((c as u32) << PSR::C_FLAG_BIT);
     }
 
     #[allow(non_snake_case)]
@@ -168,6 +194,19 @@ impl PSR {
         self.0 = (self.0 & !(1 << PSR::V_FLAG_BIT)) | ((n as u32) << PSR::V_FLAG_BIT);
     }
 
+    #[cfg(feature = "armv5te")]
+    #[allow(non_snake_case)]
+    pub fn get_Q(self) -> bool {
+        (self.0 & (1 << PSR::Q_FLAG_BIT)) != 0
+    }
+
+    // Q is sticky, it is only cleared by writing CPSR.
+    #[cfg(feature = "armv5te")]
+    #[allow(non_snake_case)]
+    pub fn set_Q(&mut self) {
+        self.0 |= 1 << PSR::Q_FLAG_BIT;
+    }
+
     pub fn set(&mut self, d: u32) {
         self.0 = d;
     }
@@ -0,0 +1,9 @@
+use types::Word;
+
+// Host side service for SWI calls, registered with `ARMv4::set_swi_handler`.
+// Lets BIOS/OS calls be stubbed in Rust instead of a guest handler at vector 0x08.
+pub trait SwiHandler {
+    // Called with the comment field and the registers of the calling mode.
+    // Returns false to leave the call to the guest SWI handler.
+    fn call(&mut self, comment: Word, gpr: &mut [Word; 16]) -> bool;
+}